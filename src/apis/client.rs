@@ -0,0 +1,438 @@
+//! Blocking client for the tarkov-market.com api.
+//!
+//! Timeouts and 5xx responses are retried with exponential backoff. A 429 waits for as long
+//! as `Retry-After` asks, and running out of quota (`X-RateLimit-Remaining: 0`) holds back
+//! the next request until `X-RateLimit-Reset`, so a burst of hotkey presses doesn't get the
+//! key throttled for longer.
+use std::{
+    fmt,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use reqwest::{
+    blocking::{Client, Response},
+    header::HeaderMap,
+    StatusCode,
+};
+
+use super::market::{parse_items, HistoryPoint, Root};
+
+/// How much of a response body to keep in an error
+const BODY_LIMIT: usize = 300;
+
+#[derive(Debug)]
+pub enum MarketError {
+    /// no response within the timeout, after all retries
+    Timeout,
+    /// couldn't connect or the connection dropped
+    Connection(String),
+    /// throttled, and waiting it out would take longer than `max_wait`
+    RateLimited { retry_after: Duration },
+    /// any other non-success status
+    Status { status: StatusCode, body: String },
+    /// a 200 that isn't a list of items
+    BadJson { error: String, body: String },
+}
+
+impl fmt::Display for MarketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarketError::Timeout => write!(f, "market api timed out"),
+            MarketError::Connection(e) => write!(f, "could not reach the market api: {e}"),
+            MarketError::RateLimited { retry_after } => {
+                write!(
+                    f,
+                    "market api rate limit, try again in {}s",
+                    retry_after.as_secs()
+                )
+            }
+            MarketError::Status { status, body } => {
+                write!(f, "market api returned {status}: {body}")
+            }
+            MarketError::BadJson { error, body } => {
+                write!(f, "bad market api response ({error}): {body}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MarketError {}
+
+/// At most `BODY_LIMIT` bytes, cut on a char boundary
+fn truncate(body: &str) -> String {
+    if body.len() <= BODY_LIMIT {
+        return body.to_owned();
+    }
+    let mut end = BODY_LIMIT;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &body[..end])
+}
+
+/// Seconds from a header like `Retry-After: 30`
+fn header_secs(headers: &HeaderMap, name: &str) -> Option<Duration> {
+    let value = headers.get(name)?.to_str().ok()?;
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+pub struct MarketClient {
+    http: Client,
+    base_url: String,
+    api_key: String,
+    /// what language item names come back in, the api's `lang` codes
+    pub lang: String,
+    /// retries after the first attempt
+    pub retries: u32,
+    /// first backoff, doubled each retry
+    pub backoff: Duration,
+    /// longest we'll sit waiting on a rate limit before giving up
+    pub max_wait: Duration,
+    /// set when the api says the quota is used up
+    blocked_until: Mutex<Option<Instant>>,
+}
+
+impl MarketClient {
+    pub fn new(base_url: impl Into<String>, api_key: impl Into<String>, timeout: Duration) -> Self {
+        Self {
+            http: Client::builder()
+                .timeout(timeout)
+                .build()
+                .expect("tls backend should initialize"),
+            base_url: base_url.into(),
+            api_key: api_key.into(),
+            lang: "en".to_owned(),
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            max_wait: Duration::from_secs(10),
+            blocked_until: Mutex::new(None),
+        }
+    }
+
+    /// Search items by name. Warnings are whatever the lenient parser had to skip or default.
+    pub fn search(&self, query: &str) -> Result<(Root, Vec<String>), MarketError> {
+        self.search_in(query, &self.lang)
+    }
+
+    /// `search`, but with names in `lang` whatever the client is set to
+    pub fn search_in(&self, query: &str, lang: &str) -> Result<(Root, Vec<String>), MarketError> {
+        let body = self.get("/api/v1/item", &[("q", query), ("lang", lang)])?;
+        parse_items(&body).map_err(|e| MarketError::BadJson {
+            error: e.to_string(),
+            body: truncate(&body),
+        })
+    }
+
+    /// Every item the market knows about, in one (large) response
+    pub fn all(&self) -> Result<(Root, Vec<String>), MarketError> {
+        let body = self.get("/api/v1/items/all", &[("lang", &self.lang)])?;
+        parse_items(&body).map_err(|e| MarketError::BadJson {
+            error: e.to_string(),
+            body: truncate(&body),
+        })
+    }
+
+    /// Price history of one item. Not every api key is allowed to ask for this.
+    pub fn history(&self, uid: &str) -> Result<Vec<HistoryPoint>, MarketError> {
+        let body = self.get("/api/v1/item/history", &[("uid", uid)])?;
+        serde_json::from_str(&body).map_err(|e| MarketError::BadJson {
+            error: e.to_string(),
+            body: truncate(&body),
+        })
+    }
+
+    fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<String, MarketError> {
+        let mut attempt = 0;
+        loop {
+            self.wait_for_quota()?;
+
+            let result = self
+                .http
+                .get(format!("{}{}", self.base_url, path))
+                .query(query)
+                .header("x-api-key", &self.api_key)
+                .send();
+
+            let error = match result {
+                Ok(response) => match self.handle(response) {
+                    Ok(body) => return Ok(body),
+                    Err(e) => e,
+                },
+                Err(e) if e.is_timeout() => MarketError::Timeout,
+                Err(e) => MarketError::Connection(e.to_string()),
+            };
+
+            let delay = match &error {
+                MarketError::RateLimited { retry_after } if *retry_after <= self.max_wait => {
+                    *retry_after
+                }
+                MarketError::Timeout | MarketError::Connection(_) => {
+                    self.backoff * 2u32.pow(attempt)
+                }
+                MarketError::Status { status, .. } if status.is_server_error() => {
+                    self.backoff * 2u32.pow(attempt)
+                }
+                _ => return Err(error),
+            };
+
+            if attempt >= self.retries {
+                return Err(error);
+            }
+            attempt += 1;
+            thread::sleep(delay);
+        }
+    }
+
+    /// Body of a successful response, remembering any quota headers on the way
+    fn handle(&self, response: Response) -> Result<String, MarketError> {
+        let status = response.status();
+        let headers = response.headers().clone();
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = header_secs(&headers, "retry-after").unwrap_or(self.backoff);
+            return Err(MarketError::RateLimited { retry_after });
+        }
+
+        if headers
+            .get("x-ratelimit-remaining")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.trim() == "0")
+        {
+            if let Some(reset) = header_secs(&headers, "x-ratelimit-reset") {
+                *self.blocked_until.lock().unwrap() = Some(Instant::now() + reset);
+            }
+        }
+
+        let body = response
+            .text()
+            .map_err(|e| MarketError::Connection(e.to_string()))?;
+        if !status.is_success() {
+            return Err(MarketError::Status {
+                status,
+                body: truncate(&body),
+            });
+        }
+        Ok(body)
+    }
+
+    /// Sleep out a used-up quota, or fail right away if that would take too long
+    fn wait_for_quota(&self) -> Result<(), MarketError> {
+        let Some(until) = self.blocked_until.lock().unwrap().take() else {
+            return Ok(());
+        };
+        let wait = until.saturating_duration_since(Instant::now());
+        if wait > self.max_wait {
+            *self.blocked_until.lock().unwrap() = Some(until);
+            return Err(MarketError::RateLimited { retry_after: wait });
+        }
+        thread::sleep(wait);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::Arc,
+    };
+
+    /// Serves `responses` in order, one per connection, and records each request line
+    fn mock_server(responses: Vec<String>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let seen = requests.clone();
+        thread::spawn(move || {
+            for (stream, response) in listener.incoming().zip(responses) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                seen.lock().unwrap().push(line.trim().to_owned());
+                // drain the headers
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                }
+                // a hung connection, on its own thread so the retry still gets answered
+                if let Some(delay) = response.strip_prefix("SLEEP ") {
+                    let delay = Duration::from_millis(delay.parse().unwrap());
+                    thread::spawn(move || {
+                        thread::sleep(delay);
+                        drop(stream);
+                    });
+                    continue;
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn reply(status: &str, headers: &[&str], body: &str) -> String {
+        let mut text = format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            body.len()
+        );
+        for header in headers {
+            text.push_str(header);
+            text.push_str("\r\n");
+        }
+        text.push_str("\r\n");
+        text.push_str(body);
+        text
+    }
+
+    fn client(url: &str) -> MarketClient {
+        let mut client = MarketClient::new(url, "key", Duration::from_millis(300));
+        client.backoff = Duration::from_millis(10);
+        client
+    }
+
+    const WATER: &str = r#"[{"name": "Bottle of water (0.6L)", "price": 12777}]"#;
+
+    #[test]
+    fn retries_server_errors() {
+        let (url, requests) = mock_server(vec![
+            reply("502 Bad Gateway", &[], "upstream down"),
+            reply("503 Service Unavailable", &[], ""),
+            reply("200 OK", &[], WATER),
+        ]);
+        let (items, _) = client(&url).search("water").unwrap();
+
+        assert_eq!(items[0].price, 12777);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0], "GET /api/v1/item?q=water&lang=en HTTP/1.1");
+    }
+
+    #[test]
+    fn asks_in_the_client_language() {
+        let (url, requests) = mock_server(vec![
+            reply("200 OK", &[], WATER),
+            reply("200 OK", &[], WATER),
+        ]);
+        let mut client = client(&url);
+        client.lang = "ru".to_owned();
+        client.search("вода").unwrap();
+        client.search_in("Dollars", "en").unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[0],
+            "GET /api/v1/item?q=%D0%B2%D0%BE%D0%B4%D0%B0&lang=ru HTTP/1.1"
+        );
+        assert_eq!(requests[1], "GET /api/v1/item?q=Dollars&lang=en HTTP/1.1");
+    }
+
+    #[test]
+    fn gives_up_with_status_and_body() {
+        let body = "x".repeat(1000);
+        let (url, _) = mock_server(vec![reply("500 Internal Server Error", &[], &body); 4]);
+
+        match client(&url).search("water") {
+            Err(MarketError::Status { status, body }) => {
+                assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+                assert_eq!(body.len(), BODY_LIMIT + '…'.len_utf8());
+            }
+            other => panic!("expected a status error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let (url, requests) = mock_server(vec![
+            reply("401 Unauthorized", &[], r#"{"error":"Invalid API key"}"#),
+            reply("200 OK", &[], WATER),
+        ]);
+
+        let result = client(&url).search("water");
+        assert!(matches!(result, Err(MarketError::Status { status, .. }) if status == 401));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn respects_retry_after() {
+        let (url, _) = mock_server(vec![
+            reply("429 Too Many Requests", &["Retry-After: 1"], ""),
+            reply("200 OK", &[], WATER),
+        ]);
+
+        let started = Instant::now();
+        assert!(client(&url).search("water").is_ok());
+        assert!(started.elapsed() >= Duration::from_secs(1));
+
+        // too long to wait out
+        let (url, requests) = mock_server(vec![reply(
+            "429 Too Many Requests",
+            &["Retry-After: 600"],
+            "",
+        )]);
+        match client(&url).search("water") {
+            Err(MarketError::RateLimited { retry_after }) => assert_eq!(retry_after.as_secs(), 600),
+            other => panic!("expected a rate limit, got {other:?}"),
+        }
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn holds_back_when_quota_runs_out() {
+        let (url, _) = mock_server(vec![reply(
+            "200 OK",
+            &["X-RateLimit-Remaining: 0", "X-RateLimit-Reset: 60"],
+            WATER,
+        )]);
+        let client = client(&url);
+        assert!(client.search("water").is_ok());
+        assert!(matches!(
+            client.search("water"),
+            Err(MarketError::RateLimited { .. })
+        ));
+    }
+
+    #[test]
+    fn timeouts_are_retried() {
+        let (url, requests) =
+            mock_server(vec!["SLEEP 1000".to_owned(), reply("200 OK", &[], WATER)]);
+        assert!(client(&url).search("water").is_ok());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn item_history() {
+        let (url, requests) = mock_server(vec![reply(
+            "200 OK",
+            &[],
+            r#"[{"price": 12000, "avgPrice": 11800, "time": "2024-01-07T12:00:00.000Z"},
+                {"price": 12777, "time": "2024-01-07T18:00:00.000Z"}]"#,
+        )]);
+        let points = client(&url).history("d43e286e").unwrap();
+
+        assert_eq!(points.len(), 2);
+        assert_eq!((points[0].price, points[0].avg_price), (12000, 11800));
+        assert_eq!(points[1].avg_price, 0);
+        assert_eq!(
+            requests.lock().unwrap()[0],
+            "GET /api/v1/item/history?uid=d43e286e HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn bad_json_keeps_the_body() {
+        let (url, _) = mock_server(vec![reply("200 OK", &[], "<html>maintenance</html>")]);
+        match client(&url).search("water") {
+            Err(MarketError::BadJson { body, .. }) => assert_eq!(body, "<html>maintenance</html>"),
+            other => panic!("expected bad json, got {other:?}"),
+        }
+    }
+}
@@ -1,15 +1,28 @@
-// Generated by https://transform.tools/json-to-rust-serde
-use serde::Deserialize;
-use serde::Serialize;
+//! Response types for the tarkov-market.com item search.
+//!
+//! The api adds, drops and renames fields now and then, so parsing is lenient: missing fields
+//! get defaults, values that don't parse (a currency we don't know, a broken url) become
+//! `None`, and an item that still won't deserialize is skipped instead of failing the whole
+//! response. `parse_items` says what it had to paper over.
+use std::{collections::BTreeSet, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use url::Url;
+
+use crate::currency::Currency;
 
 pub type Root = Vec<TarkovMarketItem>;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct TarkovMarketItem {
     pub uid: String,
     pub name: String,
     pub banned_on_flea: bool,
+    /// missing means the api didn't say, not that there's no data
+    #[serde(default = "yes")]
     pub have_market_data: bool,
     pub tags: Vec<String>,
     pub short_name: String,
@@ -19,18 +32,191 @@ pub struct TarkovMarketItem {
     pub avg7days_price: i64,
     pub trader_name: String,
     pub trader_price: i64,
-    pub trader_price_cur: String,
+    /// `None` for a currency we don't know, `trader_price_rub` is still right then
+    #[serde(deserialize_with = "lenient")]
+    pub trader_price_cur: Option<Currency>,
     pub trader_price_rub: i64,
-    pub updated: String,
+    #[serde(deserialize_with = "lenient")]
+    pub updated: Option<DateTime<Utc>>,
+    /// everything is at least 1x1, and we divide by this
+    #[serde(default = "one")]
     pub slots: i64,
     pub diff24h: f64,
     pub diff7days: f64,
-    pub icon: String,
-    pub link: String,
-    pub wiki_link: String,
-    pub img: String,
-    pub img_big: String,
+    #[serde(deserialize_with = "lenient")]
+    pub icon: Option<Url>,
+    #[serde(deserialize_with = "lenient")]
+    pub link: Option<Url>,
+    #[serde(deserialize_with = "lenient")]
+    pub wiki_link: Option<Url>,
+    #[serde(deserialize_with = "lenient")]
+    pub img: Option<Url>,
+    #[serde(deserialize_with = "lenient")]
+    pub img_big: Option<Url>,
     pub bsg_id: String,
     pub is_functional: bool,
-    pub reference: String,
+    #[serde(deserialize_with = "lenient")]
+    pub reference: Option<Url>,
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn one() -> i64 {
+    1
+}
+
+/// A string field that's `None` when it's missing, null, or doesn't parse as a `T`
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+{
+    let value = Value::deserialize(deserializer)?;
+    Ok(value.as_str().and_then(|text| text.parse().ok()))
+}
+
+/// Parse a search response, skipping items that don't deserialize. Everything that didn't
+/// match the model (unknown or missing fields, values that didn't parse, skipped items)
+/// comes back as a warning, one per item per problem.
+pub fn parse_items(text: &str) -> Result<(Root, Vec<String>), serde_json::Error> {
+    let values: Vec<Value> = serde_json::from_str(text)?;
+
+    let known: BTreeSet<String> = match serde_json::to_value(TarkovMarketItem::default()) {
+        Ok(Value::Object(fields)) => fields.into_iter().map(|(k, _)| k).collect(),
+        _ => unreachable!("TarkovMarketItem serializes to an object"),
+    };
+
+    let mut items = vec![];
+    let mut warnings = vec![];
+    for (i, value) in values.into_iter().enumerate() {
+        let Value::Object(fields) = &value else {
+            warnings.push(format!("item {i}: not an object, skipped"));
+            continue;
+        };
+        let name = fields
+            .get("name")
+            .and_then(Value::as_str)
+            .map_or_else(|| format!("item {i}"), str::to_owned);
+
+        let unknown: Vec<&str> = fields
+            .keys()
+            .filter(|k| !known.contains(*k))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            warnings.push(format!("{name}: unknown fields {}", unknown.join(", ")));
+        }
+
+        let missing: Vec<&str> = known
+            .iter()
+            .filter(|k| !fields.contains_key(*k))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            warnings.push(format!("{name}: missing fields {}", missing.join(", ")));
+        }
+
+        match TarkovMarketItem::deserialize(&value) {
+            Ok(item) => {
+                // lenient fields come back null when their value didn't parse
+                let parsed = serde_json::to_value(&item).unwrap_or_default();
+                for (key, original) in fields {
+                    if !original.is_null() && parsed.get(key) == Some(&Value::Null) {
+                        warnings.push(format!("{name}: couldn't parse {key} {original}"));
+                    }
+                }
+                items.push(item);
+            }
+            Err(e) => warnings.push(format!("{name}: {e}, skipped")),
+        }
+    }
+
+    Ok((items, warnings))
+}
+
+/// One point of an item's price history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPoint {
+    pub price: i64,
+    #[serde(default)]
+    pub avg_price: i64,
+    pub time: DateTime<Utc>,
+}
+
+/// A handful of real responses, used by `--print-table` and the tests
+pub fn sample_items() -> Root {
+    serde_json::from_str(include_str!("sample_items.json")).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> (Root, Vec<String>) {
+        parse_items(text).unwrap()
+    }
+
+    #[test]
+    fn samples_parse_cleanly() {
+        let text = include_str!("sample_items.json");
+        let (items, warnings) = parse(text);
+        assert_eq!(warnings, Vec::<String>::new());
+        assert_eq!(items, sample_items());
+
+        let water = &items[0];
+        assert_eq!(water.trader_price_cur, Some(Currency::Rouble));
+        assert_eq!(
+            water.updated.map(|t| t.to_rfc3339()),
+            Some("2024-01-07T23:54:16.662+00:00".to_owned())
+        );
+        assert_eq!(
+            water.wiki_link.as_ref().map(Url::as_str),
+            Some("https://escapefromtarkov.fandom.com/wiki/Bottle_of_water_(0.6L)")
+        );
+    }
+
+    #[test]
+    fn dollar_item() {
+        let (items, warnings) = parse(include_str!("../../tests/fixtures/market_m4a1.json"));
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(items[0].trader_price_cur, Some(Currency::Dollar));
+        assert_eq!(items[0].trader_name, "Peacekeeper");
+    }
+
+    #[test]
+    fn missing_and_unknown_fields() {
+        let (items, warnings) = parse(include_str!("../../tests/fixtures/market_drifted.json"));
+
+        // the item without slots or haveMarketData still comes through, with sane defaults
+        assert_eq!(items.len(), 2);
+        let salewa = &items[0];
+        assert_eq!(salewa.slots, 1);
+        assert!(salewa.have_market_data);
+        assert_eq!(salewa.updated, None);
+        assert_eq!(salewa.trader_price_cur, None);
+        assert_eq!(salewa.trader_price_rub, 14880);
+        assert_eq!(salewa.img_big, None);
+
+        assert_eq!(
+            warnings[..4],
+            [
+                "Salewa first aid kit: unknown fields priceHistory",
+                "Salewa first aid kit: missing fields haveMarketData, slots",
+                "Salewa first aid kit: couldn't parse traderPriceCur \"¥\"",
+                "Salewa first aid kit: couldn't parse updated \"yesterday\"",
+            ]
+        );
+        assert!(warnings.contains(&"item 1: not an object, skipped".to_owned()));
+        assert!(warnings
+            .contains(&"Broken: invalid type: string \"lots\", expected i64, skipped".to_owned()));
+        assert_eq!(items[1].name, "Bolts");
+    }
+
+    #[test]
+    fn not_a_list_is_an_error() {
+        assert!(parse_items(r#"{"error": "Invalid API key"}"#).is_err());
+    }
 }
@@ -1 +1,3 @@
+pub mod client;
 pub mod market;
+pub mod tarkov_dev;
@@ -0,0 +1,514 @@
+//! The tarkov.dev GraphQL api, for what the market api doesn't have: what quests and hideout
+//! stations ask for, ammo and armor stats, container sizes, what every trader pays for an
+//! item, and what traders charge for dollars and euros. It needs no key, and the answers
+//! rarely change, so each is kept in a file next to the config.
+use std::time::Duration;
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    details::{Ammo, Armor, Container, Details, ItemDetails},
+    report::TraderOffer,
+    requirements::{Need, Requirement},
+    sell::TraderPrices,
+};
+
+pub const GRAPHQL_URL: &str = "https://api.tarkov.dev/graphql";
+
+const REQUIREMENTS_QUERY: &str = "{
+  tasks {
+    name
+    trader { name }
+    objectives {
+      ... on TaskObjectiveItem { type count foundInRaid items { id name } }
+    }
+  }
+  hideoutStations {
+    name
+    levels {
+      level
+      itemRequirements { count item { id name } attributes { name value } }
+    }
+  }
+}";
+
+const AMMO_QUERY: &str = "{
+  ammo {
+    item { id name }
+    caliber stackMaxSize damage armorDamage penetrationPower projectileCount
+  }
+}";
+
+const GEAR_QUERY: &str = "{
+  items(types: [armor, helmet, rig, backpack, container]) {
+    id name
+    properties {
+      ... on ItemPropertiesArmor { class durability }
+      ... on ItemPropertiesHelmet { class durability }
+      ... on ItemPropertiesChestRig { class durability capacity }
+      ... on ItemPropertiesBackpack { capacity }
+      ... on ItemPropertiesContainer { capacity }
+    }
+  }
+}";
+
+const TRADER_PRICES_QUERY: &str = "{
+  items {
+    id name
+    sellFor { vendor { name normalizedName } price currency }
+  }
+}";
+
+/// The Dollars and Euros items, by bsg id
+const EXCHANGE_QUERY: &str = "{
+  items(ids: [\"5696686a4bdc2da3298b456a\", \"569668774bdc2da2298b4568\"]) {
+    id name
+    buyFor { vendor { name normalizedName } price currency }
+  }
+}";
+
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    data: T,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequirementsData {
+    tasks: Vec<Task>,
+    hideout_stations: Vec<Station>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Named {
+    #[serde(default)]
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Task {
+    name: String,
+    trader: Option<Named>,
+    objectives: Vec<Objective>,
+}
+
+/// Objectives that aren't about items come back as `{}`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Objective {
+    #[serde(rename = "type")]
+    kind: String,
+    count: i64,
+    found_in_raid: bool,
+    items: Vec<Named>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Station {
+    name: String,
+    levels: Vec<Level>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Level {
+    level: u8,
+    item_requirements: Vec<ItemRequirement>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemRequirement {
+    count: i64,
+    item: Named,
+    #[serde(default)]
+    attributes: Vec<Attribute>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Attribute {
+    name: String,
+    value: String,
+}
+
+/// Flatten a response into one requirement per item per quest or station level
+fn requirements(data: RequirementsData) -> Vec<Requirement> {
+    let mut requirements = vec![];
+
+    for task in data.tasks {
+        let trader = task.trader.map(|t| t.name).unwrap_or_default();
+        // "findItem" objectives come paired with a "giveItem" for the same items
+        for objective in task.objectives.iter().filter(|o| o.kind == "giveItem") {
+            // any one of `items` will do, they're variants of the same thing
+            requirements.extend(objective.items.iter().map(|item| Requirement {
+                item: item.name.clone(),
+                bsg_id: item.id.clone(),
+                count: objective.count,
+                found_in_raid: objective.found_in_raid,
+                needed_for: Need::Quest {
+                    name: task.name.clone(),
+                    trader: trader.clone(),
+                },
+            }));
+        }
+    }
+
+    for station in data.hideout_stations {
+        for level in station.levels {
+            requirements.extend(level.item_requirements.into_iter().map(|req| {
+                Requirement {
+                    found_in_raid: req
+                        .attributes
+                        .iter()
+                        .any(|a| a.name == "foundInRaid" && a.value == "true"),
+                    item: req.item.name,
+                    bsg_id: req.item.id,
+                    count: req.count,
+                    needed_for: Need::Hideout {
+                        station: station.name.clone(),
+                        level: level.level,
+                    },
+                }
+            }));
+        }
+    }
+
+    requirements
+}
+
+#[derive(Debug, Deserialize)]
+struct AmmoData {
+    ammo: Vec<AmmoEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AmmoEntry {
+    item: Named,
+    #[serde(default)]
+    caliber: String,
+    stack_max_size: i64,
+    damage: i64,
+    armor_damage: i64,
+    penetration_power: i64,
+    #[serde(default)]
+    projectile_count: i64,
+}
+
+fn ammo(data: AmmoData) -> Vec<ItemDetails> {
+    data.ammo
+        .into_iter()
+        .map(|entry| ItemDetails {
+            bsg_id: entry.item.id,
+            name: entry.item.name,
+            details: Details::Ammo(Ammo {
+                caliber: caliber_name(&entry.caliber),
+                damage: entry.damage,
+                armor_damage: entry.armor_damage,
+                penetration: entry.penetration_power,
+                stack: entry.stack_max_size,
+                projectiles: entry.projectile_count.max(1),
+            }),
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct GearData {
+    items: Vec<GearEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GearEntry {
+    id: String,
+    name: String,
+    properties: Option<GearProperties>,
+}
+
+/// Every kind's fields in one, missing the ones it doesn't have
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GearProperties {
+    class: i64,
+    durability: i64,
+    capacity: i64,
+}
+
+/// Anything with an armor class is armor, anything else with room inside is a container
+fn gear(data: GearData) -> Vec<ItemDetails> {
+    data.items
+        .into_iter()
+        .filter_map(|entry| {
+            let props = entry.properties?;
+            let details = if props.class > 0 {
+                Details::Armor(Armor {
+                    class: props.class,
+                    durability: props.durability,
+                    capacity: props.capacity,
+                })
+            } else if props.capacity > 0 {
+                Details::Container(Container {
+                    capacity: props.capacity,
+                })
+            } else {
+                return None;
+            };
+            Some(ItemDetails {
+                bsg_id: entry.id,
+                name: entry.name,
+                details,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct SellForData {
+    items: Vec<SellForEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SellForEntry {
+    id: String,
+    name: String,
+    #[serde(default)]
+    sell_for: Vec<SellFor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BuyForData {
+    items: Vec<BuyForEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BuyForEntry {
+    id: String,
+    name: String,
+    #[serde(default)]
+    buy_for: Vec<SellFor>,
+}
+
+/// One vendor's price, for selling an item to them or buying it from them
+#[derive(Debug, Deserialize)]
+struct SellFor {
+    vendor: Vendor,
+    price: i64,
+    currency: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Vendor {
+    name: String,
+    normalized_name: String,
+}
+
+/// Trader offers only, the flea is in `sellFor` and `buyFor` too
+fn trader_offers(prices: Vec<SellFor>) -> Vec<TraderOffer> {
+    prices
+        .into_iter()
+        .filter(|sell| sell.vendor.normalized_name != "flea-market")
+        .filter_map(|sell| {
+            Some(TraderOffer {
+                currency: sell.currency.parse().ok()?,
+                name: sell.vendor.name,
+                price: sell.price,
+            })
+        })
+        .collect()
+}
+
+/// Items no trader buys are left out
+fn trader_prices(data: SellForData) -> Vec<TraderPrices> {
+    data.items
+        .into_iter()
+        .filter_map(|entry| {
+            Some(TraderPrices {
+                bsg_id: entry.id,
+                name: entry.name,
+                offers: trader_offers(entry.sell_for),
+            })
+            .filter(|prices| !prices.offers.is_empty())
+        })
+        .collect()
+}
+
+/// What traders sell the currencies for, in the same shape as what they pay for items
+fn exchange_prices(data: BuyForData) -> Vec<TraderPrices> {
+    data.items
+        .into_iter()
+        .map(|entry| TraderPrices {
+            bsg_id: entry.id,
+            name: entry.name,
+            offers: trader_offers(entry.buy_for),
+        })
+        .collect()
+}
+
+/// `Caliber556x45NATO` => `556x45NATO`
+fn caliber_name(caliber: &str) -> String {
+    caliber
+        .strip_prefix("Caliber")
+        .unwrap_or(caliber)
+        .to_owned()
+}
+
+fn query<T: DeserializeOwned>(query: &str) -> Result<T, reqwest::Error> {
+    let response: Response<T> = reqwest::blocking::Client::new()
+        .post(GRAPHQL_URL)
+        .timeout(Duration::from_secs(30))
+        .json(&serde_json::json!({ "query": query }))
+        .send()?
+        .error_for_status()?
+        .json()?;
+    Ok(response.data)
+}
+
+pub fn fetch_requirements() -> Result<Vec<Requirement>, reqwest::Error> {
+    query(REQUIREMENTS_QUERY).map(requirements)
+}
+
+pub fn fetch_details() -> Result<Vec<ItemDetails>, reqwest::Error> {
+    let mut details = query(AMMO_QUERY).map(ammo)?;
+    details.extend(query(GEAR_QUERY).map(gear)?);
+    Ok(details)
+}
+
+pub fn fetch_trader_prices() -> Result<Vec<TraderPrices>, reqwest::Error> {
+    query(TRADER_PRICES_QUERY).map(trader_prices)
+}
+
+pub fn fetch_exchange_prices() -> Result<Vec<TraderPrices>, reqwest::Error> {
+    query(EXCHANGE_QUERY).map(exchange_prices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{currency::Currency, lang::Language};
+
+    #[test]
+    fn flatten_response() {
+        let response: Response<RequirementsData> = serde_json::from_str(include_str!(
+            "../../tests/fixtures/tarkov_dev_requirements.json"
+        ))
+        .unwrap();
+        let requirements = requirements(response.data);
+
+        let names: Vec<&str> = requirements.iter().map(|r| r.item.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "Salewa first aid kit",
+                "Golden rooster figurine",
+                "LEDX Skin Transilluminator"
+            ],
+            "find objectives and non-item objectives are dropped"
+        );
+
+        let salewa = &requirements[0];
+        assert_eq!(salewa.bsg_id, "544fb45d4bdc2dee738b4568");
+        assert_eq!(salewa.amount(), "3x found in raid");
+        assert_eq!(salewa.needed_for.to_string(), "Shortage (Therapist)");
+
+        assert_eq!(requirements[2].needed_for.to_string(), "Medstation 3");
+        assert!(requirements[2].found_in_raid);
+        assert!(!requirements[1].found_in_raid);
+    }
+
+    #[test]
+    fn ammo_details() {
+        let response: Response<AmmoData> =
+            serde_json::from_str(include_str!("../../tests/fixtures/tarkov_dev_ammo.json"))
+                .unwrap();
+        let details = ammo(response.data);
+        assert_eq!(details[0].name, "5.56x45mm M855A1");
+        let Details::Ammo(m855a1) = &details[0].details else {
+            panic!("{:?}", details[0]);
+        };
+        assert_eq!(m855a1.caliber, "556x45NATO");
+        let en = Language::En.strings();
+        assert_eq!(m855a1.summary(en), "pen 44 (class 4)  dmg 49  armor 52%");
+        assert_eq!(m855a1.stack, 60);
+
+        let Details::Ammo(buckshot) = &details[1].details else {
+            panic!("{:?}", details[1]);
+        };
+        assert_eq!(buckshot.projectiles, 8);
+    }
+
+    #[test]
+    fn gear_details() {
+        let response: Response<GearData> =
+            serde_json::from_str(include_str!("../../tests/fixtures/tarkov_dev_gear.json"))
+                .unwrap();
+        let details = gear(response.data);
+
+        let names: Vec<&str> = details.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "Team Wendy EXFIL Ballistic Helmet (Coyote Brown)",
+                "Dogtag case",
+                "BlackRock chest rig",
+                "6B5-16 Zh-86 Uley armored rig",
+            ],
+            "items without properties are left out"
+        );
+        assert_eq!(
+            details[0].details,
+            Details::Armor(Armor {
+                class: 4,
+                durability: 44,
+                capacity: 0
+            })
+        );
+        assert_eq!(
+            details[1].details,
+            Details::Container(Container { capacity: 100 })
+        );
+        assert_eq!(
+            details[3].details.summary(Language::En.strings(), 9),
+            "class 4, 60 durability, 12 slots"
+        );
+    }
+
+    #[test]
+    fn trader_offers() {
+        let response: Response<SellForData> = serde_json::from_str(include_str!(
+            "../../tests/fixtures/tarkov_dev_sell_for.json"
+        ))
+        .unwrap();
+        let prices = trader_prices(response.data);
+        assert_eq!(prices.len(), 1, "items only the flea buys are left out");
+
+        let names: Vec<&str> = prices[0].offers.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["Ragman", "Peacekeeper"],
+            "no flea, no unknown currencies"
+        );
+        assert_eq!(prices[0].offers[1].currency, Currency::Dollar);
+        assert_eq!(prices[0].offers[1].price, 18);
+    }
+
+    #[test]
+    fn currency_sellers() {
+        let response: Response<BuyForData> =
+            serde_json::from_str(include_str!("../../tests/fixtures/tarkov_dev_buy_for.json"))
+                .unwrap();
+        let prices = exchange_prices(response.data);
+        assert_eq!(prices[0].name, "Dollars");
+        assert_eq!(
+            prices[0].offers,
+            [TraderOffer {
+                name: "Peacekeeper".into(),
+                price: 146,
+                currency: Currency::Rouble,
+            }]
+        );
+        assert_eq!(prices[1].offers[0].name, "Skier");
+    }
+}
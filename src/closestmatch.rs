@@ -6,7 +6,8 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 
 /// The ```ClosestMatch``` struct stores informations about the dictionary of known words
-/// and the different sizes for the bags of words.
+/// and the different sizes for the bags of words. Words are compared in lowercase but
+/// returned as they were given.
 #[derive(Debug)]
 pub struct ClosestMatch {
     substrings: HashMap<String, HashSet<String>>,
@@ -79,7 +80,10 @@ impl ClosestMatch {
         let mut substrings = HashMap::new();
         let splitwords: Vec<SplitWord> = dictionary
             .par_iter()
-            .map(|possible| split_word(&possible.to_lowercase(), &sizes))
+            .map(|possible| SplitWord {
+                word: possible.clone(),
+                ..split_word(&possible.to_lowercase(), &sizes)
+            })
             .collect();
 
         for splitword in splitwords {
@@ -95,16 +99,19 @@ impl ClosestMatch {
     /// The function ```get_closest``` takes a word with type ```String``` and
     /// returns the closest word in the dictionary of known words.
     pub fn get_closest(&self, word: &str) -> Option<String> {
-        let word_subs = split_word(&word, &self.substring_sizes).substrings;
+        self.get_closest_scored(word).map(|(word, _)| word)
+    }
+
+    /// Same as ```get_closest```, but also returns the match score. 0.5 is a perfect match.
+    pub fn get_closest_scored(&self, word: &str) -> Option<(String, f32)> {
+        let word_subs = split_word(&word.to_lowercase(), &self.substring_sizes).substrings;
         let best = self
             .substrings
             .par_iter()
-            .map(|(possible, possible_subs)| {
-                evaluate(&word_subs, possible.to_lowercase(), possible_subs)
-            })
+            .map(|(possible, possible_subs)| evaluate(&word_subs, possible.clone(), possible_subs))
             .reduce_with(|a, b| max_score(a, b));
         match best {
-            Some(expr) => Some(expr.word),
+            Some(expr) => Some((expr.word, expr.score)),
             None => None,
         }
     }
@@ -128,4 +135,19 @@ mod tests {
         let closest = cm.get_closest("hlo");
         println!("{:?}", closest);
     }
+
+    #[test]
+    fn keeps_the_dictionary_case() {
+        let cm = ClosestMatch::new(
+            vec!["LEDX Skin Transilluminator".to_string(), "Ledx".to_string()],
+            vec![3, 4],
+        );
+        assert_eq!(
+            cm.get_closest("ledx skin transilumnator").as_deref(),
+            Some("LEDX Skin Transilluminator")
+        );
+        let (word, score) = cm.get_closest_scored("LEDX SKIN TRANSILLUMINATOR").unwrap();
+        assert_eq!(word, "LEDX Skin Transilluminator");
+        assert_eq!(score, 0.5);
+    }
 }
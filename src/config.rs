@@ -0,0 +1,88 @@
+use std::{collections::HashMap, fs, io::ErrorKind};
+
+use serde::Deserialize;
+
+use crate::{
+    fees::FeeSettings,
+    hotkeys::Action,
+    lang::{Language, NumberLocale},
+    preprocess::Step,
+    tiers::{TierBasis, TierConfig},
+    watch::{Watch, WatchSource},
+};
+
+/// Config is read from the working directory, next to the ocr models
+pub const CONFIG_PATH: &str = "config.json";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// key combo (`"t"`, `"ctrl+shift+f5"`) => action to run
+    pub hotkeys: HashMap<String, Action>,
+    /// presses of the same hotkey closer together than this are ignored
+    pub debounce_ms: u64,
+    /// how many actions can wait behind the one currently running
+    pub queue_len: usize,
+    /// how long the overlay popup stays up
+    pub overlay_seconds: u64,
+    /// market responses are reused for this long before asking the api again
+    pub cache_seconds: u64,
+    /// fee rules and hideout bonuses for the flea tax
+    pub fees: FeeSettings,
+    /// how far under the going rate to aim when suggesting a flea listing price
+    pub undercut_percent: f64,
+    /// items to poll for price alerts
+    pub watchlist: Vec<Watch>,
+    pub watch_source: WatchSource,
+    /// how often the watchlist is checked
+    pub watch_minutes: u64,
+    /// alerts are also posted here, Discord and Slack incoming webhooks both work
+    pub webhook_url: Option<String>,
+    /// value tiers for coloring prices, a preset name or a list of thresholds
+    pub tiers: TierConfig,
+    /// whether tiers go by an item's whole price or its price per slot
+    pub tier_basis: TierBasis,
+    /// the game client's language, for item names and the console's labels
+    pub language: Language,
+    /// how numbers are grouped when not the language's usual way, a `num_format` locale name
+    pub number_locale: Option<NumberLocale>,
+    /// cleanup run on the tooltip crop before ocr, in order
+    pub ocr_preprocess: Vec<Step>,
+    /// when set, every crop is saved here with the item it matched, for `ocr-eval`
+    pub ocr_corpus: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            hotkeys: HashMap::from([("t".to_owned(), Action::PriceUnderCursor)]),
+            debounce_ms: 250,
+            queue_len: 4,
+            overlay_seconds: 5,
+            cache_seconds: 300,
+            fees: FeeSettings::default(),
+            undercut_percent: 0.0,
+            watchlist: vec![],
+            watch_source: WatchSource::default(),
+            watch_minutes: 15,
+            webhook_url: None,
+            tiers: TierConfig::default(),
+            tier_basis: TierBasis::default(),
+            language: Language::default(),
+            number_locale: None,
+            ocr_preprocess: vec![],
+            ocr_corpus: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load `config.json`. A missing file just means "use the defaults".
+    pub fn load() -> Result<Self, String> {
+        match fs::read_to_string(CONFIG_PATH) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{CONFIG_PATH}: {e}")),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{CONFIG_PATH}: {e}")),
+        }
+    }
+}
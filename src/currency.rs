@@ -0,0 +1,217 @@
+//! The three currencies traders deal in, and what dollars and euros are worth in rubles.
+//!
+//! Rates are what Peacekeeper charges for dollars and Skier for euros, from the tarkov.dev api.
+//! The last good rates are kept in `rates.json` so a failed fetch, or running offline, still
+//! uses something recent instead of the launch-day numbers.
+use std::{fmt, fs, io, path::Path, str::FromStr, sync::RwLock};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::sell::TraderPrices;
+
+pub const RATES_PATH: &str = "rates.json";
+
+/// bsg ids of the currency items
+const DOLLARS_ID: &str = "5696686a4bdc2da3298b456a";
+const EUROS_ID: &str = "569668774bdc2da2298b4568";
+
+/// The only traders that sell each currency
+const DOLLAR_SELLER: &str = "Peacekeeper";
+const EURO_SELLER: &str = "Skier";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Currency {
+    #[serde(rename = "₽")]
+    Rouble,
+    #[serde(rename = "$")]
+    Dollar,
+    #[serde(rename = "€")]
+    Euro,
+}
+
+impl Currency {
+    pub fn sign(&self) -> &'static str {
+        match self {
+            Currency::Rouble => "₽",
+            Currency::Dollar => "$",
+            Currency::Euro => "€",
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.sign())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "₽" | "RUB" | "rub" => Ok(Currency::Rouble),
+            "$" | "USD" | "usd" => Ok(Currency::Dollar),
+            "€" | "EUR" | "eur" => Ok(Currency::Euro),
+            other => Err(format!("unknown currency {:?}", other)),
+        }
+    }
+}
+
+/// Rubles per dollar and per euro
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rates {
+    pub dollar: i64,
+    pub euro: i64,
+}
+
+impl Default for Rates {
+    /// What they were when this was first written, for when nothing better is around
+    fn default() -> Self {
+        Self {
+            dollar: 142,
+            euro: 160,
+        }
+    }
+}
+
+impl Rates {
+    pub fn in_rubles(&self, value: i64, currency: Currency) -> i64 {
+        match currency {
+            Currency::Rouble => value,
+            Currency::Dollar => value * self.dollar,
+            Currency::Euro => value * self.euro,
+        }
+    }
+
+    /// `rubles` worth of `currency`, rounded down
+    pub fn in_currency(&self, rubles: i64, currency: Currency) -> i64 {
+        match currency {
+            Currency::Rouble => rubles,
+            Currency::Dollar => rubles / self.dollar,
+            Currency::Euro => rubles / self.euro,
+        }
+    }
+
+    /// Take the rates from what the currency items cost at their traders, in rubles.
+    /// Returns whether anything changed.
+    pub fn update_from(&mut self, prices: &[TraderPrices]) -> bool {
+        let mut changed = false;
+        for item in prices {
+            let (rate, seller) = match item.bsg_id.as_str() {
+                DOLLARS_ID => (&mut self.dollar, DOLLAR_SELLER),
+                EUROS_ID => (&mut self.euro, EURO_SELLER),
+                _ => continue,
+            };
+            let Some(offer) = item.offers.iter().find(|offer| {
+                offer.name == seller && offer.currency == Currency::Rouble && offer.price > 0
+            }) else {
+                continue;
+            };
+            changed |= *rate != offer.price;
+            *rate = offer.price;
+        }
+        changed
+    }
+
+    /// The last rates that were fetched successfully, `None` if none ever were
+    pub fn load(path: impl AsRef<Path>) -> Option<Self> {
+        let text = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Only worth doing when a fetch changed something, see `update_from`
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// The defaults until `main` loads the cache or a fetch comes back
+static RATES: Lazy<RwLock<Rates>> = Lazy::new(|| RwLock::new(Rates::default()));
+
+/// The current rates: fetched this session, else cached, else the defaults
+pub fn rates() -> Rates {
+    *RATES.read().unwrap()
+}
+
+pub fn set_rates(rates: Rates) {
+    *RATES.write().unwrap() = rates;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{report::TraderOffer, test_util::TempPath};
+
+    fn sold_by(bsg_id: &str, trader: &str, price: i64) -> TraderPrices {
+        TraderPrices {
+            bsg_id: bsg_id.to_owned(),
+            name: String::new(),
+            offers: vec![TraderOffer {
+                name: trader.to_owned(),
+                price,
+                currency: Currency::Rouble,
+            }],
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("₽".parse(), Ok(Currency::Rouble));
+        assert_eq!("$".parse(), Ok(Currency::Dollar));
+        assert_eq!("EUR".parse(), Ok(Currency::Euro));
+        assert!("¥".parse::<Currency>().is_err());
+        assert_eq!(
+            serde_json::to_string(&Currency::Euro).unwrap(),
+            "\"€\"",
+            "history stores the sign, like it did before the enum"
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        let rates = Rates {
+            dollar: 145,
+            euro: 158,
+        };
+        assert_eq!(rates.in_rubles(10, Currency::Dollar), 1450);
+        assert_eq!(rates.in_rubles(10, Currency::Euro), 1580);
+        assert_eq!(rates.in_currency(1000, Currency::Dollar), 6);
+        assert_eq!(rates.in_currency(1000, Currency::Rouble), 1000);
+    }
+
+    #[test]
+    fn update_from_traders() {
+        let mut rates = Rates::default();
+        let items = vec![
+            sold_by(DOLLARS_ID, "Peacekeeper", 145),
+            sold_by("5448fee04bdc2dbc018b4567", "Therapist", 2450),
+            // only Skier's price counts, and a bad response shouldn't zero a rate
+            sold_by(EUROS_ID, "Fence", 180),
+            sold_by(EUROS_ID, "Skier", 0),
+        ];
+        assert!(rates.update_from(&items));
+        assert_eq!(
+            rates,
+            Rates {
+                dollar: 145,
+                euro: 160
+            }
+        );
+        assert!(!rates.update_from(&items));
+    }
+
+    #[test]
+    fn cache_roundtrip() {
+        let path = TempPath::new("rates.json");
+        assert_eq!(Rates::load(&path), None);
+
+        let rates = Rates {
+            dollar: 150,
+            euro: 170,
+        };
+        rates.save(&path).unwrap();
+        assert_eq!(Rates::load(&path), Some(rates));
+    }
+}
@@ -0,0 +1,286 @@
+//! What an item is beyond its price, for the kinds where price alone doesn't say much: ammo
+//! ballistics, armor class, and how much room a container adds.
+//!
+//! Fetched from the tarkov.dev api the first time and on `refresh`, like the quest
+//! requirements, and kept in `item_details.json`. Items are matched by bsg id, then by name.
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+    sync::RwLock,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::lang::{fill, Strings};
+
+pub const DETAILS_PATH: &str = "item_details.json";
+
+/// Highest armor class there is
+const MAX_CLASS: i64 = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ammo {
+    /// the api's caliber id without its `Caliber` prefix, `556x45NATO`
+    pub caliber: String,
+    /// per projectile
+    pub damage: i64,
+    /// percent
+    pub armor_damage: i64,
+    pub penetration: i64,
+    /// how many rounds fit in one slot
+    pub stack: i64,
+    /// buckshot fires several
+    #[serde(default = "one")]
+    pub projectiles: i64,
+}
+
+fn one() -> i64 {
+    1
+}
+
+impl Ammo {
+    /// The highest armor class it goes through reliably, 10 penetration per class
+    pub fn armor_class(&self) -> i64 {
+        (self.penetration / 10).clamp(0, MAX_CLASS)
+    }
+
+    /// `37x8` for buckshot, `49` for everything else
+    pub fn damage_text(&self) -> String {
+        if self.projectiles > 1 {
+            format!("{}x{}", self.damage, self.projectiles)
+        } else {
+            self.damage.to_string()
+        }
+    }
+
+    /// `pen 44 (class 4)  dmg 49  armor 52%`
+    pub fn summary(&self, text: &Strings) -> String {
+        fill(
+            text.ammo_summary,
+            &[
+                &self.penetration,
+                &self.armor_class(),
+                &self.damage_text(),
+                &self.armor_damage,
+            ],
+        )
+    }
+}
+
+/// Body armor, helmets and armored rigs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Armor {
+    pub class: i64,
+    pub durability: i64,
+    /// slots inside, for armored rigs
+    #[serde(default)]
+    pub capacity: i64,
+}
+
+impl Armor {
+    /// `class 4, 50 durability, 12 slots`
+    pub fn summary(&self, text: &Strings) -> String {
+        let mut summary = fill(text.armor_summary, &[&self.class, &self.durability]);
+        if self.capacity > 0 {
+            summary += &fill(text.armor_slots, &[&self.capacity]);
+        }
+        summary
+    }
+}
+
+/// Backpacks, unarmored rigs and stash containers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Container {
+    /// slots inside
+    pub capacity: i64,
+}
+
+impl Container {
+    /// How many more slots it holds than it takes up, when that's anything
+    pub fn gained(&self, outer_slots: i64) -> Option<i64> {
+        Some(self.capacity - outer_slots).filter(|&gained| gained > 0)
+    }
+
+    /// What each gained slot costs at `price`
+    pub fn per_slot_gained(&self, price: i64, outer_slots: i64) -> Option<i64> {
+        self.gained(outer_slots).map(|gained| price / gained)
+    }
+
+    /// `16 slots in 9, +7`
+    pub fn summary(&self, text: &Strings, outer_slots: i64) -> String {
+        let gained = self.gained(outer_slots).unwrap_or(0);
+        fill(
+            text.container_summary,
+            &[&self.capacity, &outer_slots, &gained],
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Details {
+    Ammo(Ammo),
+    Armor(Armor),
+    Container(Container),
+}
+
+impl Details {
+    /// Row label, next to the trader and flea rows
+    pub fn label(&self, text: &'static Strings) -> &'static str {
+        match self {
+            Details::Ammo(_) => text.ammo,
+            Details::Armor(_) => text.armor,
+            Details::Container(_) => text.holds,
+        }
+    }
+
+    pub fn summary(&self, text: &Strings, outer_slots: i64) -> String {
+        match self {
+            Details::Ammo(ammo) => ammo.summary(text),
+            Details::Armor(armor) => armor.summary(text),
+            Details::Container(container) => container.summary(text, outer_slots),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemDetails {
+    pub bsg_id: String,
+    pub name: String,
+    pub details: Details,
+}
+
+/// The stats kept from the last fetch, `None` before the first one. A file that doesn't parse
+/// may have been corrected by hand, so it's an error rather than a reason to fetch over it.
+pub fn load(path: impl AsRef<Path>) -> Result<Option<Vec<ItemDetails>>, String> {
+    let path = path.as_ref();
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("{}: {e}", path.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{}: {e}", path.display())),
+    }
+}
+
+/// Pretty printed, so one item's stats can be looked up or corrected by hand
+pub fn save(path: impl AsRef<Path>, details: &[ItemDetails]) -> io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(details)?)
+}
+
+/// Empty until `main` loads the file or a fetch comes back, so tests never see a local copy
+static DETAILS: RwLock<Vec<ItemDetails>> = RwLock::new(Vec::new());
+
+pub fn set_details(details: Vec<ItemDetails>) {
+    *DETAILS.write().unwrap() = details;
+}
+
+pub fn details_for(name: &str, bsg_id: &str) -> Option<Details> {
+    find(&DETAILS.read().unwrap(), name, bsg_id)
+}
+
+fn find(all: &[ItemDetails], name: &str, bsg_id: &str) -> Option<Details> {
+    let by_id = all
+        .iter()
+        .find(|d| !bsg_id.is_empty() && d.bsg_id == bsg_id);
+    by_id
+        .or_else(|| all.iter().find(|d| d.name.eq_ignore_ascii_case(name)))
+        .map(|d| d.details.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lang::Language, test_util::TempPath};
+
+    fn m855a1() -> Ammo {
+        Ammo {
+            caliber: "556x45NATO".into(),
+            damage: 49,
+            armor_damage: 52,
+            penetration: 44,
+            stack: 60,
+            projectiles: 1,
+        }
+    }
+
+    #[test]
+    fn ammo_summary() {
+        let en = Language::En.strings();
+        assert_eq!(m855a1().summary(en), "pen 44 (class 4)  dmg 49  armor 52%");
+        assert_eq!(
+            m855a1().summary(Language::Ru.strings()),
+            "пробитие 44 (класс 4)  урон 49  броня 52%"
+        );
+
+        let buckshot = Ammo {
+            caliber: "12g".into(),
+            damage: 37,
+            armor_damage: 26,
+            penetration: 3,
+            stack: 20,
+            projectiles: 8,
+        };
+        assert_eq!(buckshot.armor_class(), 0);
+        assert_eq!(buckshot.damage_text(), "37x8");
+    }
+
+    #[test]
+    fn armor_and_containers() {
+        let helmet = Details::Armor(Armor {
+            class: 4,
+            durability: 40,
+            capacity: 0,
+        });
+        let en = Language::En.strings();
+        assert_eq!(
+            (helmet.label(en), helmet.summary(en, 4).as_str()),
+            ("Armor", "class 4, 40 durability")
+        );
+        let de = Language::De.strings();
+        assert_eq!(
+            (helmet.label(de), helmet.summary(de, 4).as_str()),
+            ("Panzerung", "Klasse 4, 40 Haltbarkeit")
+        );
+
+        // a 3x3 case with 16 inside
+        let case = Container { capacity: 16 };
+        assert_eq!(case.summary(en, 9), "16 slots in 9, +7");
+        assert_eq!(case.per_slot_gained(311111, 9), Some(44444));
+        // a pouch that holds less than it takes
+        assert_eq!(Container { capacity: 1 }.per_slot_gained(5000, 2), None);
+    }
+
+    #[test]
+    fn match_by_id_then_name() {
+        let all = vec![ItemDetails {
+            bsg_id: "54527ac44bdc2d36668b4567".into(),
+            name: "5.56x45mm M855A1".into(),
+            details: Details::Ammo(m855a1()),
+        }];
+        assert!(find(&all, "M855A1", "54527ac44bdc2d36668b4567").is_some());
+        assert!(find(&all, "5.56x45mm m855a1", "").is_some());
+        assert_eq!(
+            find(&all, "5.56x45mm M855", "54527a984bdc2d4e668b4567"),
+            None
+        );
+    }
+
+    #[test]
+    fn load_tells_missing_from_broken() {
+        let path = TempPath::new("item_details.json");
+        assert_eq!(load(&path), Ok(None));
+
+        let all = vec![ItemDetails {
+            bsg_id: "54527ac44bdc2d36668b4567".into(),
+            name: "5.56x45mm M855A1".into(),
+            details: Details::Ammo(m855a1()),
+        }];
+        save(&path, &all).unwrap();
+        assert_eq!(load(&path), Ok(Some(all)));
+
+        fs::write(&path, "[{").unwrap();
+        assert!(load(&path).unwrap_err().contains("item_details.json"));
+    }
+}
@@ -0,0 +1,242 @@
+//! Every lookup gets appended to `history.jsonl`, one json object per line, so it survives
+//! restarts and can be reviewed with `history list`, `history stats` and `history export`.
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    path::Path,
+};
+
+use crate::{
+    report::{FleaBasis, Lookup},
+    status,
+};
+
+pub const HISTORY_PATH: &str = "history.jsonl";
+
+pub fn append(path: impl AsRef<Path>, lookup: &Lookup) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let line = serde_json::to_string(lookup)?;
+    writeln!(file, "{}", line)
+}
+
+/// Read the whole log, oldest first. Lines that don't parse (say, from a crash halfway
+/// through a write) are skipped with a warning rather than failing everything.
+pub fn load(path: impl AsRef<Path>) -> io::Result<Vec<Lookup>> {
+    let file = match fs::File::open(path.as_ref()) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+
+    let mut lookups = vec![];
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(lookup) => lookups.push(lookup),
+            Err(e) => status(format!(
+                "{}:{}: skipping bad entry: {}",
+                path.as_ref().display(),
+                i + 1,
+                e
+            )),
+        }
+    }
+
+    Ok(lookups)
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// One row per matched item, so a lookup that found three items makes three rows
+pub fn write_csv(lookups: &[Lookup], mut out: impl Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "time,ocr_text,title,score,source,name,short_name,slots,trader,trader_price,trader_currency,\
+         lowest_price,lowest_tax,avg24h_price,avg24h_tax,avg7d_price,avg7d_tax"
+    )?;
+
+    for lookup in lookups {
+        for report in &lookup.reports {
+            let mut row = vec![
+                lookup.at.to_rfc3339(),
+                csv_field(&lookup.ocr_text),
+                csv_field(&lookup.title),
+                format!("{:.3}", lookup.score),
+                format!("{:?}", lookup.source),
+                csv_field(&report.name),
+                csv_field(&report.short_name),
+                report.slots.to_string(),
+                csv_field(&report.trader.name),
+                report.trader.price.to_string(),
+                report.trader.currency.to_string(),
+            ];
+            // banned and no-data items have no flea offers, leave their columns empty
+            for basis in [FleaBasis::Lowest, FleaBasis::Avg24h, FleaBasis::Avg7d] {
+                match report.flea.iter().find(|offer| offer.basis == basis) {
+                    Some(offer) => {
+                        row.push(offer.price.to_string());
+                        row.push(offer.tax.to_string());
+                    }
+                    None => row.extend([String::new(), String::new()]),
+                }
+            }
+            writeln!(out, "{}", row.join(","))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// How one item's lowest flea price moved between the first and last time we looked it up
+#[derive(Debug, PartialEq)]
+pub struct PriceMove {
+    pub name: String,
+    pub count: usize,
+    pub first: i64,
+    pub last: i64,
+}
+
+impl PriceMove {
+    pub fn percent(&self) -> f64 {
+        if self.first == 0 {
+            return 0.0;
+        }
+        (self.last - self.first) as f64 / self.first as f64 * 100.0
+    }
+}
+
+/// Per item price movement, most looked up first. Only the best match of each lookup counts.
+pub fn price_moves(lookups: &[Lookup]) -> Vec<PriceMove> {
+    let mut moves: Vec<PriceMove> = vec![];
+    let mut index: HashMap<&str, usize> = HashMap::new();
+
+    for lookup in lookups {
+        let Some(report) = lookup.reports.first() else {
+            continue;
+        };
+        let Some(lowest) = report.flea.first() else {
+            continue;
+        };
+
+        match index.get(report.name.as_str()) {
+            Some(&i) => {
+                moves[i].count += 1;
+                moves[i].last = lowest.price;
+            }
+            None => {
+                index.insert(&report.name, moves.len());
+                moves.push(PriceMove {
+                    name: report.name.clone(),
+                    count: 1,
+                    first: lowest.price,
+                    last: lowest.price,
+                });
+            }
+        }
+    }
+
+    // stable, so ties keep first-seen order
+    moves.sort_by_key(|m| std::cmp::Reverse(m.count));
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apis::market::sample_items, report::PriceSource, test_util::TempPath};
+
+    fn lookup(index: usize, price: i64) -> Lookup {
+        let mut item = sample_items()[index].clone();
+        item.price = price;
+        Lookup::new(
+            item.short_name.clone(),
+            item.name.clone(),
+            0.42,
+            PriceSource::Live,
+            &[item],
+        )
+    }
+
+    #[test]
+    fn append_then_load() {
+        let path = TempPath::new("history.jsonl");
+
+        let a = lookup(0, 12000);
+        let b = lookup(2, 60000);
+        append(&path, &a).unwrap();
+        append(&path, &b).unwrap();
+        // a torn write shouldn't lose the rest of the log
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"at\":\n")
+            .unwrap();
+
+        assert_eq!(load(&path).unwrap(), vec![a, b]);
+    }
+
+    #[test]
+    fn missing_log_is_empty() {
+        assert!(load("definitely/not/here.jsonl").unwrap().is_empty());
+    }
+
+    #[test]
+    fn csv_rows() {
+        let mut lookups = vec![lookup(0, 12000)];
+        lookups[0].ocr_text = "Bottle, \"water\"".to_owned();
+
+        let mut out = vec![];
+        write_csv(&lookups, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = text.lines().collect();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0].split(',').count(),
+            17,
+            "header and rows should have the same columns"
+        );
+        assert!(rows[1].contains(",\"Bottle, \"\"water\"\"\",Bottle of water (0.6L),0.420,Live,"));
+        assert!(rows[1].contains(",2,Therapist,1544,₽,12000,"));
+    }
+
+    #[test]
+    fn csv_banned_item_keeps_columns() {
+        let mut out = vec![];
+        write_csv(&[lookup(4, 131200)], &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let row = text.lines().nth(1).unwrap();
+        assert_eq!(row.split(',').count(), 17);
+        assert!(row.ends_with(",Ragman,3142,₽,,,,,,"));
+    }
+
+    #[test]
+    fn moves_track_first_and_last() {
+        let lookups = vec![
+            lookup(0, 10000),
+            lookup(2, 60000),
+            lookup(0, 12000),
+            lookup(0, 15000),
+        ];
+        let moves = price_moves(&lookups);
+
+        assert_eq!(moves[0].name, "Bottle of water (0.6L)");
+        assert_eq!(
+            (moves[0].count, moves[0].first, moves[0].last),
+            (3, 10000, 15000)
+        );
+        assert_eq!(moves[0].percent(), 50.0);
+        assert_eq!(moves[1].count, 1);
+    }
+}
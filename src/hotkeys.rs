@@ -0,0 +1,453 @@
+//! Hotkey parsing and the binding table. Nothing in here touches inputbot except the
+//! `input`-gated glue at the bottom, so the lookup logic can be tested anywhere.
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use serde::Deserialize;
+
+/// Everything a hotkey can trigger
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// OCR the inspect box under the cursor and price it
+    PriceUnderCursor,
+    /// Price every item visible in the stash
+    StashScan,
+    /// Print the previous result again
+    RepeatLast,
+    /// Show or hide the overlay window
+    ToggleOverlay,
+}
+
+impl Action {
+    /// A new press of a slow lookup makes the previous one stale, so it gets cancelled
+    /// instead of queued behind it. Quick actions just run once per press.
+    pub fn supersedes_previous(&self) -> bool {
+        matches!(self, Action::PriceUnderCursor | Action::StashScan)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    fn count(&self) -> usize {
+        self.ctrl as usize + self.shift as usize + self.alt as usize
+    }
+
+    fn union(&self, other: Modifiers) -> Modifiers {
+        Modifiers {
+            ctrl: self.ctrl || other.ctrl,
+            shift: self.shift || other.shift,
+            alt: self.alt || other.alt,
+        }
+    }
+
+    /// true if every modifier in `self` is also held in `held`
+    fn satisfied_by(&self, held: Modifiers) -> bool {
+        (!self.ctrl || held.ctrl) && (!self.shift || held.shift) && (!self.alt || held.alt)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// a-z
+    Letter(char),
+    /// top row 0-9
+    Digit(u8),
+    /// F1-F24
+    Function(u8),
+    /// numpad 0-9
+    Numpad(u8),
+    Space,
+    Tab,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Backquote,
+}
+
+impl FromStr for Key {
+    type Err = HotkeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+        let unknown = || HotkeyError::UnknownKey(s.to_owned());
+
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return match c {
+                'a'..='z' => Ok(Key::Letter(c)),
+                '0'..='9' => Ok(Key::Digit(c as u8 - b'0')),
+                '`' => Ok(Key::Backquote),
+                _ => Err(unknown()),
+            };
+        }
+
+        let numbered = |prefix: &str, max: u8| {
+            name.strip_prefix(prefix)
+                .and_then(|n| n.parse::<u8>().ok())
+                .filter(|n| *n <= max)
+        };
+
+        if let Some(n) = numbered("numpad", 9) {
+            return Ok(Key::Numpad(n));
+        }
+        if let Some(n) = numbered("f", 24).filter(|n| *n >= 1) {
+            return Ok(Key::Function(n));
+        }
+
+        match name.as_str() {
+            "space" => Ok(Key::Space),
+            "tab" => Ok(Key::Tab),
+            "insert" | "ins" => Ok(Key::Insert),
+            "delete" | "del" => Ok(Key::Delete),
+            "home" => Ok(Key::Home),
+            "end" => Ok(Key::End),
+            "pageup" | "pgup" => Ok(Key::PageUp),
+            "pagedown" | "pgdn" => Ok(Key::PageDown),
+            "backquote" | "grave" => Ok(Key::Backquote),
+            _ => Err(unknown()),
+        }
+    }
+}
+
+/// A single key plus the modifiers that must be held with it, e.g. `ctrl+shift+t`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl FromStr for KeyCombo {
+    type Err = HotkeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = Modifiers::default();
+        let mut key = None;
+
+        for part in s.split('+').map(str::trim) {
+            match part.to_lowercase().as_str() {
+                "" => return Err(HotkeyError::Empty(s.to_owned())),
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" => modifiers.alt = true,
+                _ => {
+                    if key.is_some() {
+                        return Err(HotkeyError::MultipleKeys(s.to_owned()));
+                    }
+                    key = Some(part.parse()?);
+                }
+            }
+        }
+
+        let key = key.ok_or_else(|| HotkeyError::MissingKey(s.to_owned()))?;
+        Ok(KeyCombo { key, modifiers })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotkeyError {
+    Empty(String),
+    UnknownKey(String),
+    MissingKey(String),
+    MultipleKeys(String),
+    DuplicateBinding(String),
+    AmbiguousBinding(String, String),
+}
+
+impl fmt::Display for HotkeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty(s) => write!(f, "hotkey '{s}' has an empty part"),
+            Self::UnknownKey(s) => write!(f, "unknown key name '{s}'"),
+            Self::MissingKey(s) => write!(f, "hotkey '{s}' only has modifiers"),
+            Self::MultipleKeys(s) => write!(f, "hotkey '{s}' has more than one non-modifier key"),
+            Self::DuplicateBinding(s) => write!(f, "hotkey '{s}' is bound more than once"),
+            Self::AmbiguousBinding(a, b) => write!(
+                f,
+                "hotkeys '{a}' and '{b}' both match when all their modifiers are held, bind that combination too"
+            ),
+        }
+    }
+}
+
+/// Validated hotkey table
+#[derive(Debug, Clone, Default)]
+pub struct Bindings {
+    table: HashMap<KeyCombo, Action>,
+}
+
+impl Bindings {
+    pub fn new(config: &HashMap<String, Action>) -> Result<Self, HotkeyError> {
+        let mut table = HashMap::new();
+        let mut parsed = Vec::new();
+
+        for (combo_str, action) in config {
+            let combo: KeyCombo = combo_str.parse()?;
+            // "Ctrl+T" and "control+t" are the same binding
+            if table.insert(combo, *action).is_some() {
+                return Err(HotkeyError::DuplicateBinding(combo_str.clone()));
+            }
+            parsed.push((combo, combo_str));
+        }
+
+        // With ctrl and shift held, `ctrl+t` and `shift+t` both match and neither has more
+        // modifiers. Only a `ctrl+shift+t` binding says which action that should be.
+        parsed.sort_by_key(|(_, combo_str)| *combo_str);
+        for (i, (a, a_str)) in parsed.iter().enumerate() {
+            for (b, b_str) in &parsed[i + 1..] {
+                let tied = a.key == b.key && a.modifiers.count() == b.modifiers.count();
+                let both = KeyCombo {
+                    key: a.key,
+                    modifiers: a.modifiers.union(b.modifiers),
+                };
+                if tied && !table.contains_key(&both) {
+                    return Err(HotkeyError::AmbiguousBinding(
+                        (*a_str).clone(),
+                        (*b_str).clone(),
+                    ));
+                }
+            }
+        }
+
+        Ok(Self { table })
+    }
+
+    /// Every distinct key that needs a listener
+    pub fn keys(&self) -> Vec<Key> {
+        let mut keys: Vec<Key> = Vec::new();
+        for combo in self.table.keys() {
+            if !keys.contains(&combo.key) {
+                keys.push(combo.key);
+            }
+        }
+        keys
+    }
+
+    /// Find the action for `key` pressed while `held` modifiers are down.
+    ///
+    /// All of a binding's modifiers must be held; when several bindings match, the one
+    /// with the most modifiers wins, so `shift+t` beats a plain `t`. `new` already turned
+    /// away tables where two of them could tie.
+    pub fn resolve(&self, key: Key, held: Modifiers) -> Option<Action> {
+        self.table
+            .iter()
+            .filter(|(combo, _)| combo.key == key && combo.modifiers.satisfied_by(held))
+            .max_by_key(|(combo, _)| combo.modifiers.count())
+            .map(|(_, action)| *action)
+    }
+}
+
+#[cfg(feature = "input")]
+pub fn to_inputbot(key: Key) -> inputbot::KeybdKey {
+    use inputbot::KeybdKey as K;
+
+    match key {
+        Key::Letter(c) => inputbot::get_keybd_key(c).expect("letters are always mappable"),
+        Key::Digit(n) => [
+            K::Numrow0Key,
+            K::Numrow1Key,
+            K::Numrow2Key,
+            K::Numrow3Key,
+            K::Numrow4Key,
+            K::Numrow5Key,
+            K::Numrow6Key,
+            K::Numrow7Key,
+            K::Numrow8Key,
+            K::Numrow9Key,
+        ][n as usize],
+        Key::Numpad(n) => [
+            K::Numpad0Key,
+            K::Numpad1Key,
+            K::Numpad2Key,
+            K::Numpad3Key,
+            K::Numpad4Key,
+            K::Numpad5Key,
+            K::Numpad6Key,
+            K::Numpad7Key,
+            K::Numpad8Key,
+            K::Numpad9Key,
+        ][n as usize],
+        Key::Function(n) => [
+            K::F1Key,
+            K::F2Key,
+            K::F3Key,
+            K::F4Key,
+            K::F5Key,
+            K::F6Key,
+            K::F7Key,
+            K::F8Key,
+            K::F9Key,
+            K::F10Key,
+            K::F11Key,
+            K::F12Key,
+            K::F13Key,
+            K::F14Key,
+            K::F15Key,
+            K::F16Key,
+            K::F17Key,
+            K::F18Key,
+            K::F19Key,
+            K::F20Key,
+            K::F21Key,
+            K::F22Key,
+            K::F23Key,
+            K::F24Key,
+        ][n as usize - 1],
+        Key::Space => K::SpaceKey,
+        Key::Tab => K::TabKey,
+        Key::Insert => K::InsertKey,
+        Key::Delete => K::DeleteKey,
+        Key::Home => K::HomeKey,
+        Key::End => K::EndKey,
+        Key::PageUp => K::PageUpKey,
+        Key::PageDown => K::PageDownKey,
+        Key::Backquote => K::BackquoteKey,
+    }
+}
+
+#[cfg(feature = "input")]
+pub fn held_modifiers() -> Modifiers {
+    use inputbot::KeybdKey as K;
+
+    Modifiers {
+        ctrl: K::LControlKey.is_pressed() || K::RControlKey.is_pressed(),
+        shift: K::LShiftKey.is_pressed() || K::RShiftKey.is_pressed(),
+        alt: K::LAltKey.is_pressed() || K::RAltKey.is_pressed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings(pairs: &[(&str, Action)]) -> Result<Bindings, HotkeyError> {
+        let config = pairs.iter().map(|(k, a)| (k.to_string(), *a)).collect();
+        Bindings::new(&config)
+    }
+
+    #[test]
+    fn parses_key_names() {
+        assert_eq!("T".parse(), Ok(Key::Letter('t')));
+        assert_eq!("7".parse(), Ok(Key::Digit(7)));
+        assert_eq!("f12".parse(), Ok(Key::Function(12)));
+        assert_eq!("Numpad3".parse(), Ok(Key::Numpad(3)));
+        assert_eq!("pgup".parse(), Ok(Key::PageUp));
+        assert!("f0".parse::<Key>().is_err());
+        assert!("f25".parse::<Key>().is_err());
+        assert!("numpad10".parse::<Key>().is_err());
+        assert!("hyper".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn parses_combos() {
+        let combo: KeyCombo = "Ctrl + Shift + F5".parse().unwrap();
+        assert_eq!(combo.key, Key::Function(5));
+        assert!(combo.modifiers.ctrl && combo.modifiers.shift && !combo.modifiers.alt);
+
+        assert!(matches!(
+            "ctrl+shift".parse::<KeyCombo>(),
+            Err(HotkeyError::MissingKey(_))
+        ));
+        assert!(matches!(
+            "t+y".parse::<KeyCombo>(),
+            Err(HotkeyError::MultipleKeys(_))
+        ));
+        assert!(matches!(
+            "ctrl++t".parse::<KeyCombo>(),
+            Err(HotkeyError::Empty(_))
+        ));
+        assert!(matches!(
+            "ctrl+nope".parse::<KeyCombo>(),
+            Err(HotkeyError::UnknownKey(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicates_after_normalizing() {
+        let result = bindings(&[
+            ("ctrl+t", Action::PriceUnderCursor),
+            ("Control+T", Action::RepeatLast),
+        ]);
+        assert!(matches!(result, Err(HotkeyError::DuplicateBinding(_))));
+    }
+
+    #[test]
+    fn rejects_ties_nothing_settles() {
+        let result = bindings(&[
+            ("ctrl+t", Action::PriceUnderCursor),
+            ("shift+t", Action::RepeatLast),
+        ]);
+        assert_eq!(
+            result.unwrap_err(),
+            HotkeyError::AmbiguousBinding("ctrl+t".into(), "shift+t".into())
+        );
+
+        let b = bindings(&[
+            ("ctrl+t", Action::PriceUnderCursor),
+            ("shift+t", Action::RepeatLast),
+            ("ctrl+shift+t", Action::StashScan),
+        ])
+        .unwrap();
+        let ctrl_shift = Modifiers {
+            ctrl: true,
+            shift: true,
+            alt: false,
+        };
+        assert_eq!(
+            b.resolve(Key::Letter('t'), ctrl_shift),
+            Some(Action::StashScan)
+        );
+    }
+
+    #[test]
+    fn most_specific_binding_wins() {
+        let b = bindings(&[
+            ("t", Action::PriceUnderCursor),
+            ("shift+t", Action::RepeatLast),
+            ("ctrl+shift+t", Action::StashScan),
+            ("f9", Action::ToggleOverlay),
+        ])
+        .unwrap();
+
+        let t = Key::Letter('t');
+        let none = Modifiers::default();
+        let shift = Modifiers {
+            shift: true,
+            ..none
+        };
+        let ctrl = Modifiers { ctrl: true, ..none };
+        let ctrl_shift = Modifiers {
+            ctrl: true,
+            shift: true,
+            ..none
+        };
+
+        assert_eq!(b.resolve(t, none), Some(Action::PriceUnderCursor));
+        assert_eq!(b.resolve(t, shift), Some(Action::RepeatLast));
+        assert_eq!(b.resolve(t, ctrl), Some(Action::PriceUnderCursor));
+        assert_eq!(b.resolve(t, ctrl_shift), Some(Action::StashScan));
+        assert_eq!(
+            b.resolve(Key::Function(9), ctrl),
+            Some(Action::ToggleOverlay)
+        );
+        assert_eq!(b.resolve(Key::Letter('y'), none), None);
+
+        let mut keys = b.keys();
+        keys.sort_by_key(|k| format!("{k:?}"));
+        assert_eq!(keys, vec![Key::Function(9), Key::Letter('t')]);
+    }
+
+    #[test]
+    fn modifier_only_bindings_need_their_modifiers() {
+        let b = bindings(&[("alt+f1", Action::RepeatLast)]).unwrap();
+        assert_eq!(b.resolve(Key::Function(1), Modifiers::default()), None);
+    }
+}
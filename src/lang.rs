@@ -0,0 +1,364 @@
+//! The game client's language: which item names OCR is matched against, what language the
+//! market api answers in, the labels next to prices, and how numbers are grouped.
+//!
+//! English names are built in (`wiki_titles.txt`). Any other language reads its names from
+//! `titles_<code>.txt` in the working directory, one per line. When there isn't one it's made
+//! from the market's item list in that language.
+use std::{fmt, fs, io, path::Path};
+
+use num_format::Locale;
+use serde::Deserialize;
+
+/// The languages the market api has names in, by its `lang` codes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    Ru,
+    De,
+    Fr,
+    Es,
+    Cn,
+    Cz,
+    Hu,
+    Tr,
+}
+
+impl Language {
+    /// What the market api calls it
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::Ru => "ru",
+            Language::De => "de",
+            Language::Fr => "fr",
+            Language::Es => "es",
+            Language::Cn => "cn",
+            Language::Cz => "cz",
+            Language::Hu => "hu",
+            Language::Tr => "tr",
+        }
+    }
+
+    /// How numbers are written there, unless the config picks something else
+    pub fn number_locale(self) -> Locale {
+        match self {
+            Language::En => Locale::en,
+            Language::Ru => Locale::ru,
+            Language::De => Locale::de,
+            Language::Fr => Locale::fr,
+            Language::Es => Locale::es,
+            Language::Cn => Locale::zh,
+            Language::Cz => Locale::cs,
+            Language::Hu => Locale::hu,
+            Language::Tr => Locale::tr,
+        }
+    }
+
+    /// Where the item names are kept, `None` for the built in English ones
+    pub fn titles_path(self) -> Option<String> {
+        match self {
+            Language::En => None,
+            lang => Some(format!("titles_{}.txt", lang.code())),
+        }
+    }
+
+    /// Labels for the console. Languages without a translation get English.
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Language::Ru => &RU,
+            Language::De => &DE,
+            _ => &EN,
+        }
+    }
+}
+
+/// A `num_format` locale name, `"de"` or `"en-IN"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct NumberLocale(pub Locale);
+
+impl TryFrom<String> for NumberLocale {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Locale::from_name(&name)
+            .map(NumberLocale)
+            .map_err(|_| format!("unknown number locale {name:?}"))
+    }
+}
+
+/// Everything the console, dashboard and overlay write besides names and numbers. `{}` are
+/// filled in order by `fill`.
+#[derive(Debug)]
+pub struct Strings {
+    pub name: &'static str,
+    pub updated: &'static str,
+    pub needed: &'static str,
+    pub needed_for: &'static str,
+    pub ammo: &'static str,
+    pub armor: &'static str,
+    pub holds: &'static str,
+    pub ammo_summary: &'static str,
+    pub armor_summary: &'static str,
+    pub armor_slots: &'static str,
+    pub container_summary: &'static str,
+    pub stack: &'static str,
+    pub stack_rounds: &'static str,
+    pub per_slot_gained: &'static str,
+    pub flea: &'static str,
+    pub flea_locked: &'static str,
+    pub flea_open: &'static str,
+    pub flea_banned: &'static str,
+    pub flea_no_data: &'static str,
+    pub lowest: &'static str,
+    pub avg_24h: &'static str,
+    pub avg_7d: &'static str,
+    pub after_tax: &'static str,
+    pub list_at: &'static str,
+    pub trend: &'static str,
+    pub stats: &'static str,
+    pub sell_to: &'static str,
+    pub over: &'static str,
+    pub hold: &'static str,
+    pub barter: &'static str,
+    pub craft: &'static str,
+    pub each_at: &'static str,
+    pub trade_in: &'static str,
+}
+
+static EN: Strings = Strings {
+    name: "Name",
+    updated: "Updated {} ago",
+    needed: "Needed",
+    needed_for: "{} for {}",
+    ammo: "Ammo",
+    armor: "Armor",
+    holds: "Holds",
+    ammo_summary: "pen {} (class {})  dmg {}  armor {}%",
+    armor_summary: "class {}, {} durability",
+    armor_slots: ", {} slots",
+    container_summary: "{} slots in {}, +{}",
+    stack: "Stack",
+    stack_rounds: "{} rounds at {}₽ = {}₽",
+    per_slot_gained: " = {}₽ per slot",
+    flea: "Flea",
+    flea_locked: "not unlocked yet, prices for reference",
+    flea_open: "open",
+    flea_banned: "banned",
+    flea_no_data: "no market data",
+    lowest: "Lowest",
+    avg_24h: "24h",
+    avg_7d: "7d",
+    after_tax: " = {}₽ - {}k₽ tax",
+    list_at: "List at",
+    trend: "Trend",
+    stats: "min {}₽  median {}₽  max {}₽ ({} samples)",
+    sell_to: "Sell to {}",
+    over: " (+{}₽ over {})",
+    hold: "  lowest is {}% under the {}d median, holding may pay",
+    barter: "Barter",
+    craft: "Craft",
+    each_at: "{}₽ each at {} for {}",
+    trade_in: "  worth {}₽ more traded in at {} than sold",
+};
+
+static RU: Strings = Strings {
+    name: "Название",
+    updated: "Обновлено {} назад",
+    needed: "Нужно",
+    needed_for: "{} для {}",
+    ammo: "Патрон",
+    armor: "Броня",
+    holds: "Вмещает",
+    ammo_summary: "пробитие {} (класс {})  урон {}  броня {}%",
+    armor_summary: "класс {}, прочность {}",
+    armor_slots: ", {} слотов",
+    container_summary: "{} слотов в {}, +{}",
+    stack: "Пачка",
+    stack_rounds: "{} шт. по {}₽ = {}₽",
+    per_slot_gained: " = {}₽ за слот",
+    flea: "Барахолка",
+    flea_locked: "ещё не открыта, цены для справки",
+    flea_open: "открыта",
+    flea_banned: "запрещено",
+    flea_no_data: "нет данных рынка",
+    lowest: "Мин.",
+    avg_24h: "24ч",
+    avg_7d: "7д",
+    after_tax: " = {}₽ - {}k₽ налог",
+    list_at: "Выставить",
+    trend: "Тренд",
+    stats: "мин {}₽  медиана {}₽  макс {}₽ ({} замеров)",
+    sell_to: "Продать: {}",
+    over: " (на {}₽ больше, чем {})",
+    hold: "  минимум на {}% ниже медианы за {}д, можно придержать",
+    barter: "Бартер",
+    craft: "Крафт",
+    each_at: "{}₽ за шт. в {} за {}",
+    trade_in: "  на {}₽ выгоднее обменять в {}, чем продать",
+};
+
+static DE: Strings = Strings {
+    name: "Name",
+    updated: "Aktualisiert vor {}",
+    needed: "Benötigt",
+    needed_for: "{} für {}",
+    ammo: "Munition",
+    armor: "Panzerung",
+    holds: "Fasst",
+    ammo_summary: "Durchschlag {} (Klasse {})  Schaden {}  Panzerung {}%",
+    armor_summary: "Klasse {}, {} Haltbarkeit",
+    armor_slots: ", {} Plätze",
+    container_summary: "{} Plätze in {}, +{}",
+    stack: "Stapel",
+    stack_rounds: "{} Schuss zu {}₽ = {}₽",
+    per_slot_gained: " = {}₽ pro Platz",
+    flea: "Flohmarkt",
+    flea_locked: "noch nicht freigeschaltet, Preise zur Orientierung",
+    flea_open: "offen",
+    flea_banned: "gesperrt",
+    flea_no_data: "keine Marktdaten",
+    lowest: "Tiefst",
+    avg_24h: "24h",
+    avg_7d: "7T",
+    after_tax: " = {}₽ - {}k₽ Gebühr",
+    list_at: "Einstellen",
+    trend: "Trend",
+    stats: "min {}₽  Median {}₽  max {}₽ ({} Werte)",
+    sell_to: "Verkaufen an {}",
+    over: " (+{}₽ gegenüber {})",
+    hold: "  Tiefstpreis {}% unter dem {}T-Median, Halten könnte sich lohnen",
+    barter: "Tausch",
+    craft: "Herstellung",
+    each_at: "{}₽ pro Stück bei {} für {}",
+    trade_in: "  {}₽ mehr wert bei {} eingetauscht als verkauft",
+};
+
+/// `template` with each `{}` replaced by the next of `args`
+pub fn fill(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut args = args.iter();
+    let mut pieces = template.split("{}");
+    let mut text = pieces.next().unwrap_or_default().to_owned();
+    for piece in pieces {
+        if let Some(arg) = args.next() {
+            text += &arg.to_string();
+        }
+        text += piece;
+    }
+    text
+}
+
+/// The item names in a language pack, one per line. `None` when there's no pack yet, or an
+/// empty one, so it gets made from the market's list.
+pub fn load_titles(path: impl AsRef<Path>) -> Option<Vec<String>> {
+    let text = fs::read_to_string(path).ok()?;
+    let titles: Vec<String> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect();
+    Some(titles).filter(|titles| !titles.is_empty())
+}
+
+/// One name per line, the same as a pack written by hand
+pub fn save_titles(path: impl AsRef<Path>, titles: &[String]) -> io::Result<()> {
+    fs::write(path, titles.join("\n") + "\n")
+}
+
+/// Every distinct name in a market item list, sorted
+pub fn titles<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut titles: Vec<String> = names
+        .into_iter()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .collect();
+    titles.sort();
+    titles.dedup();
+    titles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+    use num_format::ToFormattedString;
+
+    fn templates(strings: &Strings) -> Vec<&'static str> {
+        vec![
+            strings.updated,
+            strings.needed_for,
+            strings.ammo_summary,
+            strings.armor_summary,
+            strings.armor_slots,
+            strings.container_summary,
+            strings.stack_rounds,
+            strings.per_slot_gained,
+            strings.after_tax,
+            strings.stats,
+            strings.sell_to,
+            strings.over,
+            strings.hold,
+            strings.each_at,
+            strings.trade_in,
+        ]
+    }
+
+    #[test]
+    fn translations_fill_the_same_blanks() {
+        let blanks = |strings| -> Vec<usize> {
+            templates(strings)
+                .iter()
+                .map(|t| t.matches("{}").count())
+                .collect()
+        };
+        for lang in [Language::Ru, Language::De] {
+            assert_eq!(blanks(lang.strings()), blanks(&EN), "{lang:?}");
+        }
+        assert_eq!(
+            Language::Hu.strings().flea,
+            "Flea",
+            "english until translated"
+        );
+    }
+
+    #[test]
+    fn fill_in_order() {
+        assert_eq!(
+            fill(EN.needed_for, &[&"2x", &"Shortage (Therapist)"]),
+            "2x for Shortage (Therapist)"
+        );
+        assert_eq!(fill(RU.updated, &[&"5m"]), "Обновлено 5m назад");
+        // missing arguments leave the blank empty rather than panicking
+        assert_eq!(fill("{} and {}", &[&1]), "1 and ");
+    }
+
+    #[test]
+    fn number_locales() {
+        let config: NumberLocale = serde_json::from_str(r#""de""#).unwrap();
+        assert_eq!(1234567.to_formatted_string(&config.0), "1.234.567");
+        assert_eq!(
+            1234567.to_formatted_string(&Language::Ru.number_locale()),
+            "1\u{a0}234\u{a0}567"
+        );
+        assert!(serde_json::from_str::<NumberLocale>(r#""klingon""#).is_err());
+    }
+
+    #[test]
+    fn language_packs() {
+        let lang: Language = serde_json::from_str(r#""ru""#).unwrap();
+        assert_eq!(lang.titles_path().as_deref(), Some("titles_ru.txt"));
+        assert_eq!(Language::En.titles_path(), None);
+
+        let names = titles(["Патроны", " Бутылка воды ", "Патроны", ""]);
+        assert_eq!(names, ["Бутылка воды", "Патроны"]);
+
+        let path = TempPath::new("titles_ru.txt");
+        assert_eq!(load_titles(&path), None);
+        save_titles(&path, &names).unwrap();
+        assert_eq!(load_titles(&path), Some(names));
+    }
+}
@@ -1,81 +1,489 @@
-use std::{error::Error, fs};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
 
-use apis::market::TarkovMarketItem;
+use apis::client::{MarketClient, MarketError};
 use clap::Parser;
 use closestmatch::ClosestMatch;
-use colored::{ColoredString, Colorize};
+use colored::{Color, ColoredString, Colorize};
+use config::Config;
+use currency::Currency;
+use hotkeys::{Action, Bindings};
 use ocrs::{OcrEngine, OcrEngineParams};
-use once_cell::{self, sync::Lazy};
+use once_cell::{
+    self,
+    sync::{Lazy, OnceCell},
+};
+use prices::{PriceStore, Sample, SampleSource, WindowStats};
+use profile::Profile;
+use recipes::{InputValue, RecipeBook};
+use report::{FleaStatus, ItemReport, Lookup, PriceSource};
 use rten::Model;
 use rten_tensor::NdTensorView;
 use screenshot::{CursorPos, ScreenshotData};
+use watch::{Alert, Watch, WatchSource};
+use worker::{CancelToken, Worker};
 
 mod apis;
 mod closestmatch;
+mod config;
+mod currency;
+mod details;
+mod fees;
+mod history;
+mod hotkeys;
+mod lang;
+mod ocr_eval;
+mod overlay;
+mod preprocess;
+mod prices;
+mod profile;
+mod recipes;
+mod report;
+mod requirements;
 mod screenshot;
+mod sell;
+mod stash;
+#[cfg(test)]
+mod test_util;
+mod tiers;
+mod top;
+mod trend;
+mod tui;
+mod watch;
+mod worker;
 
 static MARKET_API_KEY: Lazy<String> =
     Lazy::new(|| include_str!("../market_api_key.txt").trim().to_owned());
 
-static WORDS: Lazy<ClosestMatch> = Lazy::new(|| {
-    let titles = include_str!("../wiki_titles.txt");
-    ClosestMatch::new(
-        titles.lines().map(|x| x.to_owned()).collect(),
-        vec![3, 4, 5, 6],
-    )
+static MARKET: Lazy<MarketClient> = Lazy::new(|| market_client(Duration::from_secs(10)));
+
+fn market_client(timeout: Duration) -> MarketClient {
+    let mut client = MarketClient::new("https://api.tarkov-market.app", &*MARKET_API_KEY, timeout);
+    client.lang = config().language.code().to_owned();
+    client
+}
+
+/// Item names OCR text is matched against, in the client's language when there's a pack for it
+static WORDS: Lazy<RwLock<ClosestMatch>> = Lazy::new(|| {
+    let titles = config()
+        .language
+        .titles_path()
+        .and_then(lang::load_titles)
+        .unwrap_or_else(|| {
+            let titles = include_str!("../wiki_titles.txt");
+            titles.lines().map(|x| x.to_owned()).collect()
+        });
+    RwLock::new(word_matcher(titles))
+});
+
+fn word_matcher(titles: Vec<String>) -> ClosestMatch {
+    ClosestMatch::new(titles, vec![3, 4, 5, 6])
+}
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+/// The loaded config, or the defaults when running without one (tests)
+fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+static PROFILE: OnceCell<Profile> = OnceCell::new();
+
+/// The loaded profile, or a fresh account when running without one
+fn profile() -> &'static Profile {
+    PROFILE.get_or_init(Profile::default)
+}
+
+/// The most recent successful lookup, for `Action::RepeatLast`
+static LAST_RESULT: Mutex<Option<Lookup>> = Mutex::new(None);
+
+/// Market responses by search text, so looking at the same item twice doesn't spend an api call
+static MARKET_CACHE: Lazy<Mutex<HashMap<String, (Instant, apis::market::Root)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Price samples from lookups and the api's item history, for the weekly stats
+static PRICES: Lazy<Mutex<PriceStore>> = Lazy::new(|| {
+    Mutex::new(PriceStore::open(prices::PRICES_PATH).unwrap_or_else(|e| {
+        status(format!("{}: {}", prices::PRICES_PATH, e));
+        PriceStore::empty(prices::PRICES_PATH)
+    }))
 });
 
+/// Barters and crafts, for what an item is worth as an input
+static RECIPES: Lazy<RecipeBook> = Lazy::new(|| {
+    RecipeBook::load(recipes::RECIPES_PATH).unwrap_or_else(|e| {
+        status(e);
+        RecipeBook::default()
+    })
+});
+
+/// The full item list `top` saved, for pricing recipe parts nobody has looked up
+static ITEM_SNAPSHOT: Lazy<apis::market::Root> =
+    Lazy::new(|| top::load(top::SNAPSHOT_PATH).unwrap_or_default());
+
+/// Cleared once the api says our key can't fetch item history, so we stop asking
+static BACKEND_HISTORY: AtomicBool = AtomicBool::new(true);
+
+/// How far back the price stats look
+const STATS_WINDOW: chrono::TimeDelta = chrono::TimeDelta::days(7);
+
+static OVERLAY_ENABLED: AtomicBool = AtomicBool::new(true);
+
 #[derive(clap::Parser)]
 struct Cli {
     /// print out a color table to show all the tier values
     #[arg(short, long)]
     print_table: bool,
+
+    /// show a dashboard with lookup history instead of printing to the console
+    #[arg(long)]
+    tui: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// look through past lookups
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+    /// rank every item by rubles per slot, for deciding what to pick up
+    Top {
+        /// only items with one of these tags, like `Barter` or `Provisions`
+        #[arg(short, long)]
+        tag: Vec<String>,
+        #[arg(short, long, default_value_t = 30)]
+        limit: usize,
+        /// download a fresh snapshot of every item first
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// compare ocr preprocessing chains on a saved corpus of tooltip crops
+    OcrEval {
+        /// the directory with the crops and labels.tsv, `ocr_corpus` from the config by default
+        corpus: Option<std::path::PathBuf>,
+        /// a json file of chain name => steps, a few presets by default
+        #[arg(long)]
+        chains: Option<std::path::PathBuf>,
+    },
+    /// download quest and hideout requirements, item stats and trader prices again, replacing
+    /// requirements.json, item_details.json and trader_prices.json
+    Refresh,
+}
+
+#[derive(clap::Subcommand)]
+enum HistoryCommand {
+    /// print the most recent lookups
+    List {
+        #[arg(short, long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// summarize lookups and how prices moved over time
+    Stats,
+    /// write every lookup as csv, to a file or stdout
+    Export { path: Option<std::path::PathBuf> },
 }
 
 fn main() {
     let cli = Cli::parse();
+    let config = match Config::load() {
+        Ok(c) => CONFIG.get_or_init(|| c),
+        Err(e) => {
+            println!("Could not load config: {}", e);
+            return;
+        }
+    };
+
+    match Profile::load() {
+        Ok(p) => PROFILE.get_or_init(|| p),
+        Err(e) => {
+            println!("Could not load profile: {}", e);
+            return;
+        }
+    };
+
+    if let Some(cached) = currency::Rates::load(currency::RATES_PATH) {
+        currency::set_rates(cached);
+    }
+    let have_requirements = match requirements::load(requirements::REQUIREMENTS_PATH) {
+        Ok(loaded) => loaded.map(requirements::set_requirements).is_some(),
+        // fetching would overwrite the file, leave it for the user to fix
+        Err(e) => {
+            status(e);
+            true
+        }
+    };
+    let have_details = match details::load(details::DETAILS_PATH) {
+        Ok(loaded) => loaded.map(details::set_details).is_some(),
+        Err(e) => {
+            status(e);
+            true
+        }
+    };
+    let trader_prices_readable = match sell::load(sell::TRADER_PRICES_PATH) {
+        Ok(loaded) => {
+            if let Some(loaded) = loaded {
+                sell::set_trader_prices(loaded);
+            }
+            true
+        }
+        Err(e) => {
+            status(e);
+            false
+        }
+    };
+
     if cli.print_table {
         print_color_table();
         return;
     }
 
-    input();
+    match cli.command {
+        Some(Command::History { command }) => {
+            if let Err(e) = history_command(command) {
+                println!("{}: {}", history::HISTORY_PATH, e);
+            }
+            return;
+        }
+        Some(Command::Top {
+            tag,
+            limit,
+            refresh,
+        }) => {
+            top_command(&tag, limit, refresh);
+            return;
+        }
+        Some(Command::OcrEval { corpus, chains }) => {
+            ocr_eval_command(corpus, chains);
+            return;
+        }
+        Some(Command::Refresh) => {
+            refresh_command();
+            return;
+        }
+        None => {}
+    }
+
+    let bindings = match Bindings::new(&config.hotkeys) {
+        Ok(b) => b,
+        Err(e) => {
+            println!("Invalid hotkey config: {}", e);
+            return;
+        }
+    };
+
+    std::thread::spawn(refresh_rates);
+    if !have_requirements {
+        std::thread::spawn(fetch_requirements);
+    }
+    if !have_details {
+        std::thread::spawn(fetch_details);
+    }
+    if trader_prices_readable {
+        std::thread::spawn(keep_trader_prices_fresh);
+    }
+    if let Some(path) = config.language.titles_path() {
+        if lang::load_titles(&path).is_none() {
+            std::thread::spawn(move || fetch_titles(&path));
+        }
+    }
+
+    watch::spawn(
+        config.watchlist.clone(),
+        Duration::from_secs(config.watch_minutes * 60),
+        watch_prices,
+        notify,
+    );
+
+    let worker = Worker::spawn(
+        config.queue_len,
+        Duration::from_millis(config.debounce_ms),
+        run_action,
+    );
+
+    if cli.tui {
+        std::thread::spawn(move || input(bindings, worker));
+        if let Err(e) = tui::run(requery) {
+            println!("Dashboard failed: {}", e);
+        }
+        return;
+    }
+
+    input(bindings, worker);
     //println!("{}", WORDS.get_closest("water ootle wit filter Aquamari").unwrap());
     //println!("{}", *MARKET_API_KEY);
 }
 
 #[cfg(feature = "input")]
-fn input() {
-    inputbot::KeybdKey::TKey.bind(|| {
-        std::thread::spawn(move || {
-            match analyze_pressed() {
-                Ok(_) => {}
-                Err(e) => {
-                    println!("{:?}", e);
+fn input(bindings: Bindings, worker: Worker) {
+    use worker::Submitted;
+
+    let bindings = std::sync::Arc::new(bindings);
+    let worker = std::sync::Arc::new(worker);
+
+    for key in bindings.keys() {
+        let bindings = bindings.clone();
+        let worker = worker.clone();
+        hotkeys::to_inputbot(key).bind(move || {
+            if let Some(action) = bindings.resolve(key, hotkeys::held_modifiers()) {
+                if worker.submit(action) == Submitted::QueueFull {
+                    status(format!("Still busy, ignoring {:?}", action));
                 }
-            };
-            println!("");
+            }
         });
-    });
-
-    println!("Bot ready");
+    }
 
-    //create_window();
+    status("Bot ready".to_owned());
 
     let t = std::thread::spawn(|| inputbot::handle_input_events());
 
     t.join().unwrap();
 }
 #[cfg(not(feature = "input"))]
-fn input() {}
+fn input(_bindings: Bindings, _worker: Worker) {}
+
+fn run_action(action: Action, token: &CancelToken) {
+    match action {
+        Action::PriceUnderCursor => match analyze_pressed(token) {
+            Ok(_) => {}
+            Err(AnalyzeError::Cancelled) => {
+                status("Lookup replaced by a newer one".to_owned());
+            }
+            Err(e) => {
+                status(format!("{:?}", e));
+            }
+        },
+        Action::StashScan => match scan_stash(token) {
+            Ok(_) => {}
+            Err(AnalyzeError::Cancelled) => {
+                status("Stash scan replaced by a newer one".to_owned());
+            }
+            Err(e) => {
+                status(format!("{:?}", e));
+            }
+        },
+        Action::RepeatLast => match LAST_RESULT.lock().unwrap().clone() {
+            Some(last) => show_results(last, Some(CursorPos::get())),
+            None => status("Nothing has been looked up yet".to_owned()),
+        },
+        Action::ToggleOverlay => {
+            let enabled = !OVERLAY_ENABLED.fetch_xor(true, Ordering::SeqCst);
+            status(format!(
+                "Overlay {}",
+                if enabled { "enabled" } else { "disabled" }
+            ));
+        }
+    }
+    if !tui::is_active() {
+        println!();
+    }
+}
+
+/// One line of progress or error text. Goes to the dashboard's status bar when it's up.
+fn status(msg: String) {
+    if let Err(tui::Event::Status(msg)) = tui::send(tui::Event::Status(msg)) {
+        println!("{}", msg);
+    }
+}
+
+/// Current reports for a watchlist entry, from the api or from the lookup history
+fn watch_prices(watch: &Watch) -> Vec<ItemReport> {
+    match config().watch_source {
+        WatchSource::Api if watch.by_bsg_id() => watch_by_bsg_id(&watch.item),
+        WatchSource::Api => match lookup_prices(&watch.item, true) {
+            Ok((items, _)) => items.iter().map(ItemReport::new).collect(),
+            Err(e) => {
+                status(format!("Watchlist: {}: {:?}", watch.item, e));
+                vec![]
+            }
+        },
+        // newest first, so the latest lookup of an item is the one that gets checked
+        WatchSource::Local => history::load(history::HISTORY_PATH)
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+            .flat_map(|lookup| lookup.reports)
+            .collect(),
+    }
+}
+
+/// The api only searches by name, so search for the item's English name and keep the result
+/// with the right id
+fn watch_by_bsg_id(bsg_id: &str) -> Vec<ItemReport> {
+    let Some(name) = sell::name_for(bsg_id) else {
+        status(format!(
+            "Watchlist: {bsg_id}: no name known for this bsg id yet"
+        ));
+        return vec![];
+    };
+    match MARKET.search_in(&name, lang::Language::En.code()) {
+        Ok((items, _)) => items
+            .iter()
+            .filter(|item| item.bsg_id == bsg_id)
+            .map(ItemReport::new)
+            .collect(),
+        Err(e) => {
+            status(format!("Watchlist: {bsg_id}: {e}"));
+            vec![]
+        }
+    }
+}
+
+fn notify(alert: Alert) {
+    status(format!("Watchlist: {}: {}", alert.item, alert.message));
+
+    let config = config();
+    if OVERLAY_ENABLED.load(Ordering::SeqCst) {
+        overlay::show(
+            std::slice::from_ref(&alert.report),
+            CursorPos::get(),
+            Duration::from_secs(config.overlay_seconds),
+        );
+    }
+
+    if let Some(url) = &config.webhook_url {
+        if let Err(e) = watch::post_webhook(url, &alert) {
+            status(format!("Could not post alert to the webhook: {}", e));
+        }
+    }
+}
+
+/// Re-fetch prices for a lookup picked from the dashboard history
+fn requery(previous: Lookup) {
+    std::thread::spawn(move || match lookup_prices(&previous.title, false) {
+        Ok((items, source)) => {
+            let lookup = Lookup::new(
+                previous.ocr_text,
+                previous.title,
+                previous.score,
+                source,
+                &items,
+            );
+            record(&lookup);
+            show_results(lookup, None);
+        }
+        Err(e) => status(format!("{:?}", e)),
+    });
+}
 
 #[derive(Debug)]
 enum AnalyzeError {
+    Cancelled,
     ScreenshotFailed,
     CannotFindInspectBox,
     InvalidOcr,
-    BadRequest(&'static str),
-    BadMarketJson,
+    Market(MarketError),
     NoCloseWord(String),
     Other(Box<dyn Error>),
 }
@@ -86,6 +494,12 @@ impl From<Box<dyn Error>> for AnalyzeError {
     }
 }
 
+impl From<MarketError> for AnalyzeError {
+    fn from(value: MarketError) -> Self {
+        Self::Market(value)
+    }
+}
+
 fn find_top_left_corner(screen: &ScreenshotData, mouse_location: &CursorPos) -> Option<(u32, u32)> {
     let mut x_edge = None;
     let mut y_edge = None;
@@ -131,27 +545,24 @@ fn find_top_left_corner(screen: &ScreenshotData, mouse_location: &CursorPos) ->
     }
 }
 
-///Get the cost to list the item on the flea market. formula from wike
-fn get_flea_tax(value_to_traders: i64, list_price: i64) -> i64 {
-    let v_o = list_price as f64;
-    let v_r = (value_to_traders as f64) * 2.0; //sell price * 2 = buy price from trade
-    let t_i = 0.05;
-    let t_r = 0.05;
-    let p_r = (v_r / v_o).log10();
-    let p_o = (v_o / v_r).log10();
-
-    let flea_price = v_o * t_i * 4f64.powf(p_o) + v_r * t_r * 4f64.powf(p_r);
-
-    flea_price as i64
+impl CancelToken {
+    fn check(&self) -> Result<(), AnalyzeError> {
+        if self.is_cancelled() {
+            Err(AnalyzeError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
 }
 
-fn analyze_pressed() -> Result<(), AnalyzeError> {
+fn analyze_pressed(token: &CancelToken) -> Result<(), AnalyzeError> {
     let mouse_location = CursorPos::get();
 
     let screen = screenshot::take_screenshot().map_err(|_| AnalyzeError::ScreenshotFailed)?;
 
     let tl_corner =
         find_top_left_corner(&screen, &mouse_location).ok_or(AnalyzeError::CannotFindInspectBox)?;
+    token.check()?;
 
     let h = 30;
     let w = 500;
@@ -159,25 +570,83 @@ fn analyze_pressed() -> Result<(), AnalyzeError> {
 
     let i = screen.to_image().unwrap();
     let subimage = image::SubImage::new(&i, tl_corner.0 + offset, tl_corner.1, w, h);
-    let subimage = subimage.to_image();
+    let crop = subimage.to_image();
 
-    //let (width, height) = subimage.dimensions();
-    let layout = subimage.sample_layout();
+    let engine = ocr_engine()?;
+    let valid_text = read_text(&engine, &preprocess::apply(&crop, &config().ocr_preprocess))?;
+    token.check()?;
 
-    let image_tensor = NdTensorView::from_slice(
-        subimage.as_raw().as_slice(),
-        [h as usize, w as usize, 3],
-        Some([
-            layout.height_stride,
-            layout.width_stride,
-            layout.channel_stride,
-        ]),
-    )
-    .unwrap()
-    .permuted([2, 0, 1]) // HWC => CHW
-    .to_tensor() // Make tensor contiguous, which makes `map` faster
-    .map(|x| *x as f32 / 255.); // Rescale from [0, 255] to [0, 1]
+    // We have pretty strict text detection, just assume the first match is the text
+    let text_ocr = valid_text.get(0).ok_or(AnalyzeError::InvalidOcr)?;
+
+    // Find the closest matching tarkov item
+    let (text, score) = WORDS
+        .read()
+        .unwrap()
+        .get_closest_scored(&text_ocr)
+        .ok_or_else(|| AnalyzeError::NoCloseWord(text_ocr.to_string()))?;
+
+    status(format!(
+        "Detected text was '{}'. Closest was '{}'. Reading market data... ",
+        &text_ocr, &text
+    ));
+    if let Some(dir) = &config().ocr_corpus {
+        if let Err(e) = ocr_eval::save_sample(dir, &crop, &text) {
+            status(format!("{dir}: {e}"));
+        }
+    }
+
+    let (items_to_price, source) = lookup_prices(&text, true)?;
+    token.check()?;
+
+    let lookup = Lookup::new(text_ocr.to_owned(), text, score, source, &items_to_price);
+    record(&lookup);
+    *LAST_RESULT.lock().unwrap() = Some(lookup.clone());
+    show_results(lookup, Some(mouse_location));
+
+    Ok(())
+}
+
+/// Read every icon label on screen and price them all from the `top` snapshot
+fn scan_stash(token: &CancelToken) -> Result<(), AnalyzeError> {
+    if ITEM_SNAPSHOT.is_empty() {
+        status(format!(
+            "Stash scan prices from {}, run `top` once to download it",
+            top::SNAPSHOT_PATH
+        ));
+        return Ok(());
+    }
+
+    let screen = screenshot::take_screenshot().map_err(|_| AnalyzeError::ScreenshotFailed)?;
+    let image = screen.to_image().unwrap();
+    let lines = read_text(&ocr_engine()?, &image)?;
+    token.check()?;
+
+    let rows = stash::scan(&lines, &ITEM_SNAPSHOT);
+    if !tui::is_active() {
+        for row in &rows {
+            println!(
+                "{:<50} {:>3} x {:>9}₽  {}₽ at {}",
+                row.name,
+                row.count,
+                format_value(row.rubles),
+                color_currency(row.total(), Currency::Rouble),
+                row.venue.label(config().language.strings()),
+            );
+        }
+    }
+    let total: i64 = rows.iter().map(stash::Row::total).sum();
+    status(format!(
+        "Stash scan found {} items worth {}₽",
+        rows.iter().map(|row| row.count).sum::<usize>(),
+        format_value(total)
+    ));
+
+    Ok(())
+}
 
+/// The OCR models, read from the working directory
+fn ocr_engine() -> Result<OcrEngine, AnalyzeError> {
     // https://github.com/robertknight/ocrs/blob/main/ocrs/examples/hello_ocr.rs
     let detection_model_data =
         fs::read("text-detection.rten").expect("Could not find text-detection.rten");
@@ -192,6 +661,28 @@ fn analyze_pressed() -> Result<(), AnalyzeError> {
         recognition_model: Some(recognition_model),
         ..Default::default()
     })?;
+    Ok(engine)
+}
+
+/// Every line of text OCR finds in `subimage`, top to bottom
+fn read_text(engine: &OcrEngine, subimage: &image::RgbImage) -> Result<Vec<String>, AnalyzeError> {
+    let (w, h) = subimage.dimensions();
+    let layout = subimage.sample_layout();
+
+    let image_tensor = NdTensorView::from_slice(
+        subimage.as_raw().as_slice(),
+        [h as usize, w as usize, 3],
+        Some([
+            layout.height_stride,
+            layout.width_stride,
+            layout.channel_stride,
+        ]),
+    )
+    .unwrap()
+    .permuted([2, 0, 1]) // HWC => CHW
+    .to_tensor() // Make tensor contiguous, which makes `map` faster
+    .map(|x| *x as f32 / 255.); // Rescale from [0, 255] to [0, 1]
+
     // Apply standard image pre-processing expected by this library (convert
     // to greyscale, map range to [-0.5, 0.5]).
     let ocr_input = engine.prepare_input(image_tensor.view())?;
@@ -204,155 +695,787 @@ fn analyze_pressed() -> Result<(), AnalyzeError> {
 
     // Phase 3: Recognize text
     let line_texts = engine.recognize_text(&ocr_input, &line_rects)?;
-    let valid_text: Vec<_> = line_texts
+    Ok(line_texts
         .iter()
         .flatten()
         // Filter likely spurious detections. With future model improvements
         // this should become unnecessary.
         .map(|l| l.to_string())
         .filter(|l| l.len() > 1)
-        .collect();
+        .collect())
+}
 
-    // We have pretty strict text detection, just assume the first match is the text
-    let text_ocr = valid_text.get(0).ok_or(AnalyzeError::InvalidOcr)?;
+/// Prices for `text`, from the cache if `allow_cached` and it's recent enough
+fn lookup_prices(
+    text: &str,
+    allow_cached: bool,
+) -> Result<(apis::market::Root, PriceSource), AnalyzeError> {
+    let ttl = Duration::from_secs(config().cache_seconds);
 
-    // Find the closest matching tarkov item
-    let text = WORDS
-        .get_closest(&text_ocr)
-        .ok_or_else(|| AnalyzeError::NoCloseWord(text_ocr.to_string()))?;
+    if allow_cached {
+        if let Some((at, items)) = MARKET_CACHE.lock().unwrap().get(text) {
+            if at.elapsed() < ttl {
+                return Ok((items.clone(), PriceSource::Cached));
+            }
+        }
+    }
+
+    let items = fetch_market(text)?;
+    MARKET_CACHE
+        .lock()
+        .unwrap()
+        .insert(text.to_owned(), (Instant::now(), items.clone()));
+    Ok((items, PriceSource::Live))
+}
+
+/// Append to the history log. Failing to write history shouldn't stop the lookup.
+fn record(lookup: &Lookup) {
+    if let Err(e) = history::append(history::HISTORY_PATH, lookup) {
+        status(format!("Could not write {}: {}", history::HISTORY_PATH, e));
+    }
+
+    let mut prices = PRICES.lock().unwrap();
+    for report in lookup.reports.iter().filter(|r| !r.uid.is_empty()) {
+        let Some(lowest) = report.flea.first() else {
+            continue;
+        };
+        let sample = Sample {
+            uid: report.uid.clone(),
+            at: lookup.at,
+            price: lowest.price,
+            source: SampleSource::Lookup,
+        };
+        if let Err(e) = prices.record(sample) {
+            status(format!("Could not write {}: {}", prices::PRICES_PATH, e));
+            break;
+        }
+    }
+    drop(prices);
+
+    if let Some(best) = lookup.reports.first() {
+        fetch_price_history(&best.uid);
+    }
+}
+
+/// Pull the api's price history for `uid` in the background, at most once a day per item.
+/// It shows up in the stats from the next lookup on.
+fn fetch_price_history(uid: &str) {
+    if uid.is_empty() || !BACKEND_HISTORY.load(Ordering::SeqCst) {
+        return;
+    }
+    let fetched = PRICES.lock().unwrap().latest(uid, SampleSource::Backend);
+    if fetched.is_some_and(|at| chrono::Utc::now() - at < chrono::TimeDelta::days(1)) {
+        return;
+    }
+
+    let uid = uid.to_owned();
+    std::thread::spawn(move || match MARKET.history(&uid) {
+        Ok(points) => {
+            if let Err(e) = PRICES.lock().unwrap().merge_backend(&uid, &points) {
+                status(format!("Could not write {}: {}", prices::PRICES_PATH, e));
+            }
+        }
+        Err(MarketError::Status { status: code, .. }) if code.is_client_error() => {
+            BACKEND_HISTORY.store(false, Ordering::SeqCst);
+            status(format!(
+                "Price history isn't available with this api key ({code})"
+            ));
+        }
+        Err(e) => status(format!("Could not fetch price history: {e}")),
+    });
+}
+
+fn history_command(command: HistoryCommand) -> std::io::Result<()> {
+    let lookups = history::load(history::HISTORY_PATH)?;
+
+    match command {
+        HistoryCommand::List { limit } => {
+            for lookup in lookups.iter().skip(lookups.len().saturating_sub(limit)) {
+                let best = lookup.reports.first();
+                let time = lookup.at.with_timezone(&chrono::Local);
+                let flea = best.and_then(|r| {
+                    let offer = r.flea.first()?;
+                    Some(color_item(offer.net(), Currency::Rouble, r.slots))
+                });
+                println!(
+                    "{}  {:<40} {}₽  (read '{}', score {:.2}, {:?})",
+                    time.format("%Y-%m-%d %H:%M"),
+                    best.map_or(lookup.title.as_str(), |r| r.name.as_str()),
+                    flea.unwrap_or_else(|| "-".normal()),
+                    lookup.ocr_text,
+                    lookup.score,
+                    lookup.source,
+                );
+            }
+        }
+        HistoryCommand::Stats => {
+            let (Some(first), Some(last)) = (lookups.first(), lookups.last()) else {
+                println!("No lookups yet");
+                return Ok(());
+            };
+            let cached = lookups
+                .iter()
+                .filter(|l| l.source == PriceSource::Cached)
+                .count();
+
+            println!(
+                "{} lookups ({} cached) from {} to {}",
+                lookups.len(),
+                cached,
+                first.at.with_timezone(&chrono::Local).format("%Y-%m-%d"),
+                last.at.with_timezone(&chrono::Local).format("%Y-%m-%d"),
+            );
+            println!();
+            println!("Most looked up, lowest flea price first seen => last seen:");
+            for m in history::price_moves(&lookups).iter().take(15) {
+                let change = format!("{:+.1}%", m.percent());
+                let change = if m.last >= m.first {
+                    change.green()
+                } else {
+                    change.red()
+                };
+                println!(
+                    "{:>4}x {:<40} {}₽ => {}₽ ({})",
+                    m.count,
+                    m.name,
+                    color_currency(m.first, Currency::Rouble),
+                    color_currency(m.last, Currency::Rouble),
+                    change,
+                );
+            }
+        }
+        HistoryCommand::Export { path } => match path {
+            Some(path) => history::write_csv(&lookups, fs::File::create(path)?)?,
+            None => history::write_csv(&lookups, std::io::stdout().lock())?,
+        },
+    }
+
+    Ok(())
+}
+
+fn top_command(tags: &[String], limit: usize, refresh: bool) {
+    let items = match top::load(top::SNAPSHOT_PATH) {
+        Some(items) if !refresh => items,
+        _ => {
+            println!("Downloading every item, this takes a while...");
+            let items = match download_items() {
+                Ok(items) => items,
+                Err(e) => {
+                    println!("Could not download the item list: {e}");
+                    return;
+                }
+            };
+            if let Err(e) = top::save(top::SNAPSHOT_PATH, &items) {
+                println!("{}: {}", top::SNAPSHOT_PATH, e);
+            }
+            items
+        }
+    };
+
+    let ranked = top::rank(&items, tags);
+    if ranked.is_empty() {
+        println!("No items matched");
+        return;
+    }
+    for (i, row) in ranked.iter().take(limit).enumerate() {
+        println!(
+            "{:>3}. {:<50} {:>9}₽/slot  {} x {}₽ at {}",
+            i + 1,
+            row.name,
+            color_currency(row.per_slot, Currency::Rouble),
+            row.slots,
+            format_value(row.rubles),
+            row.venue.label(config().language.strings()),
+        );
+    }
+}
+
+fn ocr_eval_command(corpus: Option<std::path::PathBuf>, chains: Option<std::path::PathBuf>) {
+    let Some(dir) = corpus.or_else(|| config().ocr_corpus.clone().map(Into::into)) else {
+        println!(
+            "No corpus, pass a directory or set ocr_corpus in {}",
+            config::CONFIG_PATH
+        );
+        return;
+    };
+    let labels = dir.join(ocr_eval::LABELS_FILE);
+    let samples = match ocr_eval::load_corpus(&dir) {
+        Ok(corpus) => {
+            if corpus.unverified > 0 {
+                println!(
+                    "{}: skipped {} unverified, check their names and remove the leading {}",
+                    labels.display(),
+                    corpus.unverified,
+                    ocr_eval::UNVERIFIED
+                );
+            }
+            if corpus.samples.is_empty() {
+                println!("{}: no verified samples", dir.display());
+                return;
+            }
+            corpus.samples
+        }
+        Err(e) => {
+            println!("{}: {}", labels.display(), e);
+            return;
+        }
+    };
+
+    let chains: Vec<(String, Vec<preprocess::Step>)> = match chains {
+        Some(path) => {
+            let parsed = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()));
+            match parsed {
+                Ok(chains) => BTreeMap::into_iter(chains).collect(),
+                Err(e) => {
+                    println!("{}: {}", path.display(), e);
+                    return;
+                }
+            }
+        }
+        None => {
+            let mut chains = preprocess::presets();
+            if !config().ocr_preprocess.is_empty() {
+                chains.push(("configured".to_owned(), config().ocr_preprocess.clone()));
+            }
+            chains
+        }
+    };
 
+    let mut images = vec![];
+    for sample in &samples {
+        match image::open(&sample.path) {
+            Ok(image) => images.push(image.into_rgb8()),
+            Err(e) => {
+                println!("{}: {}", sample.path.display(), e);
+                return;
+            }
+        }
+    }
+    let engine = match ocr_engine() {
+        Ok(engine) => engine,
+        Err(e) => {
+            println!("Could not load the ocr models: {e:?}");
+            return;
+        }
+    };
+
+    println!("{} samples from {}", samples.len(), dir.display());
     println!(
-        "Detected text was '{}'. Closest was '{}'. Reading market data... ",
-        &text_ocr, &text
+        "{:<16} {:>8} {:>8} {:>8} {:>9}",
+        "chain", "exact", "matched", "chars", "ms/image"
     );
+    for (name, chain) in &chains {
+        let start = Instant::now();
+        let reads: Vec<(String, Option<String>)> = images
+            .iter()
+            .map(|image| {
+                let text = read_text(&engine, &preprocess::apply(image, chain))
+                    .ok()
+                    .and_then(|lines| lines.into_iter().next())
+                    .unwrap_or_default();
+                let matched = WORDS.read().unwrap().get_closest_scored(&text);
+                (text, matched.map(|(name, _)| name))
+            })
+            .collect();
+        let per_image = start.elapsed().as_millis() / images.len() as u128;
 
-    let client = reqwest::blocking::Client::new();
-    let d = client
-        .get("https://api.tarkov-market.app/api/v1/item")
-        .query(&[("q", &text)])
-        .header("x-api-key", &*MARKET_API_KEY)
-        .send()
-        .map_err(|_| AnalyzeError::BadRequest("Something went wrong with the tarkov market api"))?;
-
-    let text = d.text().unwrap();
-
-    let items_to_price: apis::market::Root = serde_json::from_str(&text).map_err(|e| {
-        // if we fail, just dump the whole payload
-        dbg!(text);
-        dbg!(e);
-        AnalyzeError::BadMarketJson
-    })?;
+        let score = ocr_eval::score(samples.iter().zip(&reads).map(|(sample, (text, matched))| {
+            ocr_eval::Read {
+                expected: &sample.expected,
+                ocr_text: text,
+                matched: matched.as_deref(),
+            }
+        }));
+        let percent = |n: usize| n as f64 * 100.0 / score.samples as f64;
+        println!(
+            "{:<16} {:>7.0}% {:>7.0}% {:>7.1}% {:>9}",
+            name,
+            percent(score.exact),
+            percent(score.matched),
+            score.char_accuracy * 100.0,
+            per_image,
+        );
+    }
+}
+
+/// The market's whole item list, in the client's language
+fn download_items() -> Result<apis::market::Root, MarketError> {
+    // the full list is a few megabytes, more than the lookup timeout allows for
+    let (items, _) = market_client(Duration::from_secs(120)).all()?;
+    Ok(items)
+}
 
-    for item in items_to_price {
-        print_item(&item);
+fn fetch_market(text: &str) -> Result<apis::market::Root, AnalyzeError> {
+    let (items, warnings) = MARKET.search(text)?;
+    for warning in warnings {
+        status(format!("market api: {warning}"));
     }
 
-    Ok(())
+    Ok(items)
+}
+
+/// Ask tarkov.dev what traders charge for dollars and euros today, falling back to the cached
+/// rates on failure
+fn refresh_rates() {
+    let mut rates = currency::rates();
+    let fetched = match apis::tarkov_dev::fetch_exchange_prices() {
+        Ok(prices) => rates.update_from(&prices),
+        Err(e) => {
+            status(format!("Could not fetch exchange rates: {e}"));
+            false
+        }
+    };
+
+    currency::set_rates(rates);
+    if fetched {
+        if let Err(e) = rates.save(currency::RATES_PATH) {
+            status(format!("{}: {}", currency::RATES_PATH, e));
+        }
+    }
 }
 
-fn format_slots(value_in: ColoredString, cur_type: &str, item: &TarkovMarketItem) -> String {
-    if item.slots > 1 {
+/// Fetch the data that's otherwise only fetched when its file is missing, like after a wipe
+/// or a patch
+fn refresh_command() {
+    fetch_requirements();
+    fetch_details();
+    fetch_trader_prices();
+}
+
+/// Get quest and hideout requirements from tarkov.dev and keep them for next time
+fn fetch_requirements() {
+    match apis::tarkov_dev::fetch_requirements() {
+        Ok(fetched) => {
+            match requirements::save(requirements::REQUIREMENTS_PATH, &fetched) {
+                Ok(()) => status(format!(
+                    "Saved {} quest and hideout requirements to {}",
+                    fetched.len(),
+                    requirements::REQUIREMENTS_PATH
+                )),
+                Err(e) => status(format!("{}: {}", requirements::REQUIREMENTS_PATH, e)),
+            }
+            requirements::set_requirements(fetched);
+        }
+        Err(e) => status(format!(
+            "Could not fetch quest and hideout requirements: {e}"
+        )),
+    }
+}
+
+/// Make the language pack at `path` from the market's item names, and match against it from
+/// now on
+fn fetch_titles(path: &str) {
+    match download_items() {
+        Ok(items) => {
+            let titles = lang::titles(items.iter().map(|item| item.name.as_str()));
+            if let Err(e) = lang::save_titles(path, &titles) {
+                status(format!("{path}: {e}"));
+            }
+            *WORDS.write().unwrap() = word_matcher(titles);
+        }
+        Err(e) => status(format!("Could not fetch item names: {e}")),
+    }
+}
+
+/// Get ammo stats from tarkov.dev and keep them for next time
+fn fetch_details() {
+    match apis::tarkov_dev::fetch_details() {
+        Ok(fetched) => {
+            match details::save(details::DETAILS_PATH, &fetched) {
+                Ok(()) => status(format!(
+                    "Saved stats for {} items to {}",
+                    fetched.len(),
+                    details::DETAILS_PATH
+                )),
+                Err(e) => status(format!("{}: {}", details::DETAILS_PATH, e)),
+            }
+            details::set_details(fetched);
+        }
+        Err(e) => status(format!("Could not fetch item details: {e}")),
+    }
+}
+
+/// Fetch trader prices whenever the saved ones are missing or a day old, for as long as we run
+fn keep_trader_prices_fresh() {
+    loop {
+        if sell::age(sell::TRADER_PRICES_PATH).is_none_or(|age| age >= sell::MAX_AGE) {
+            fetch_trader_prices();
+        }
+        // a failed fetch leaves the file as old as it was, so this also retries hourly
+        std::thread::sleep(Duration::from_secs(60 * 60));
+    }
+}
+
+/// Get what every trader pays from tarkov.dev and keep it for next time
+fn fetch_trader_prices() {
+    match apis::tarkov_dev::fetch_trader_prices() {
+        Ok(fetched) => {
+            match sell::save(sell::TRADER_PRICES_PATH, &fetched) {
+                Ok(()) => status(format!(
+                    "Saved trader prices for {} items to {}",
+                    fetched.len(),
+                    sell::TRADER_PRICES_PATH
+                )),
+                Err(e) => status(format!("{}: {}", sell::TRADER_PRICES_PATH, e)),
+            }
+            sell::set_trader_prices(fetched);
+        }
+        Err(e) => status(format!("Could not fetch trader prices: {e}")),
+    }
+}
+
+/// Print to the console (or the dashboard), and pop up the overlay next to `at` if it's on
+fn show_results(lookup: Lookup, at: Option<CursorPos>) {
+    if let Some(at) = at.filter(|_| OVERLAY_ENABLED.load(Ordering::SeqCst)) {
+        let config = config();
+        overlay::show(
+            &lookup.reports,
+            at,
+            Duration::from_secs(config.overlay_seconds),
+        );
+    }
+
+    if let Err(tui::Event::Lookup(lookup)) = tui::send(tui::Event::Lookup(lookup)) {
+        let since = chrono::Utc::now() - STATS_WINDOW;
+        for report in &lookup.reports {
+            let (recent, stats) = {
+                let prices = PRICES.lock().unwrap();
+                (
+                    prices.recent(&report.uid, SPARKLINE_LEN),
+                    prices.window(&report.uid, since),
+                )
+            };
+            let uses = RECIPES.input_values(report, known_report);
+            print_item(report, &recent, stats, &uses);
+        }
+    }
+}
+
+/// The report for another part of a recipe, from items we already have prices for: this
+/// session's market responses, whatever their age, then the `top` snapshot. Never asks the api,
+/// a popup shouldn't wait on one call per part.
+fn known_report(part: &recipes::Part) -> Option<ItemReport> {
+    let find = |items: &apis::market::Root| {
+        items
+            .iter()
+            .find(|item| part.matches(&item.name, &item.bsg_id))
+            .map(ItemReport::new)
+    };
+    let cache = MARKET_CACHE.lock().unwrap();
+    let mut responses: Vec<_> = cache.values().collect();
+    responses.sort_by_key(|(at, _)| std::cmp::Reverse(*at));
+    responses
+        .into_iter()
+        .find_map(|(_, items)| find(items))
+        .or_else(|| find(&ITEM_SNAPSHOT))
+}
+
+fn format_slots(value_in: ColoredString, currency: Currency, report: &ItemReport) -> String {
+    if report.slots > 1 {
         format!(
             " ({}{} x {})",
             value_in,
-            cur_type,
-            item.slots.to_string().bright_yellow(),
+            currency,
+            report.slots.to_string().bright_yellow(),
         )
     } else {
         format!("")
     }
 }
 
-fn print_item(item: &TarkovMarketItem) {
-    println!("Name: {} ({})", item.name.red(), item.short_name.italic());
+/// How many past price samples go into a sparkline
+const SPARKLINE_LEN: usize = 20;
+
+fn color_change(percent: f64) -> ColoredString {
+    let text = trend::format_change(percent);
+    match trend::Direction::of(percent) {
+        trend::Direction::Up => text.green(),
+        trend::Direction::Down => text.red(),
+        trend::Direction::Flat => text.dimmed(),
+    }
+}
+
+fn print_item(
+    report: &ItemReport,
+    recent: &[i64],
+    stats: Option<WindowStats>,
+    uses: &[InputValue],
+) {
+    let text = config().language.strings();
+    println!(
+        "{}: {} ({})",
+        text.name,
+        report.name.red(),
+        report.short_name.italic()
+    );
+    if let Some(updated) = report.updated {
+        let age = report::format_age(chrono::Utc::now() - updated);
+        println!("{}", lang::fill(text.updated, &[&age.dimmed()]));
+    }
+    for requirement in &report.needed {
+        println!(
+            "{}\t{}",
+            text.needed,
+            lang::fill(
+                text.needed_for,
+                &[&requirement.amount().bold(), &requirement.needed_for]
+            )
+        );
+    }
+    match &report.details {
+        Some(details::Details::Ammo(ammo)) => {
+            let round = report.unit_price();
+            println!(
+                "{}\t{}  {}",
+                text.ammo,
+                ammo.caliber.dimmed(),
+                ammo.summary(text).bold()
+            );
+            println!(
+                "{}\t{}",
+                text.stack,
+                lang::fill(
+                    text.stack_rounds,
+                    &[
+                        &ammo.stack,
+                        &color_currency(round, Currency::Rouble),
+                        &color_currency(round * ammo.stack, Currency::Rouble),
+                    ]
+                )
+            );
+        }
+        Some(details::Details::Container(container)) => {
+            let per_slot = container
+                .per_slot_gained(report.unit_price(), report.slots)
+                .map_or_else(String::new, |price| {
+                    lang::fill(
+                        text.per_slot_gained,
+                        &[&color_currency(price, Currency::Rouble)],
+                    )
+                });
+            println!(
+                "{}\t{}{per_slot}",
+                text.holds,
+                container.summary(text, report.slots).bold()
+            );
+        }
+        Some(details::Details::Armor(armor)) => {
+            println!("{}\t{}", text.armor, armor.summary(text).bold());
+        }
+        None => {}
+    }
 
     // If this is a larger than 1x1, then display the per-slot value too
-    let slot_value = color_currency(item.trader_price / item.slots, &item.trader_price_cur);
-    let slots = format_slots(slot_value, &item.trader_price_cur, item);
+    let trader = &report.trader;
+    let slot_value = color_currency(trader.price / report.slots, trader.currency);
+    let slots = format_slots(slot_value, trader.currency, report);
 
     println!(
         "{}  \t{}{}{slots}",
-        item.trader_name,
-        color_currency(item.trader_price, &item.trader_price_cur),
-        item.trader_price_cur,
+        trader.name,
+        color_item(trader.price, trader.currency, report.slots),
+        trader.currency,
     );
 
-    // the flea tax is based on how much the trader buys it for
-    let trader_ruble_value = ruble_value(item.trader_price, &item.trader_price_cur);
-    for (price, why) in &[
-        (item.price, "Lowest"),
-        (item.avg24h_price, "24h"),
-        (item.avg7days_price, "7d "),
-    ] {
-        let price = *price;
-        let flea_tax = get_flea_tax(trader_ruble_value, price);
-        let rub = "₽";
+    if report.flea_status != FleaStatus::Open {
+        println!("{}\t{}", text.flea, report.flea_status.label(text).yellow());
+    } else if !profile().flea_unlocked {
+        println!("{}\t{}", text.flea, text.flea_locked.yellow());
+    }
+
+    for offer in &report.flea {
+        let rub = Currency::Rouble;
 
-        let slot_value = color_currency((price - flea_tax) / item.slots, rub);
-        let slots = format_slots(slot_value, rub, item);
+        let slot_value = color_currency(offer.net() / report.slots, rub);
+        let slots = format_slots(slot_value, rub, report);
 
         println!(
-            "{} Flea\t{}₽{slots} = {}₽ - {}k₽ tax",
-            why,
-            color_currency(price - flea_tax, &rub),
-            color_currency(price, &rub),
-            flea_tax / 1000,
+            "{:<3} {}\t{}₽{slots}{}",
+            offer.basis.label(text),
+            text.flea,
+            color_item(offer.net(), rub, report.slots),
+            lang::fill(
+                text.after_tax,
+                &[
+                    &color_item(offer.price, rub, report.slots),
+                    &(offer.tax / 1000)
+                ]
+            ),
         );
     }
-}
 
-fn ruble_value(value: i64, cur_type: &str) -> i64 {
-    match cur_type {
-        "₽" => value,
-        "$" => value * 142,
-        "€" => value * 160,
-        _ => unreachable!(),
+    if let Some(suggested) = &report.suggested {
+        let rub = Currency::Rouble;
+
+        let slot_value = color_currency(suggested.net() / report.slots, rub);
+        let slots = format_slots(slot_value, rub, report);
+
+        println!(
+            "{}\t{}₽{slots}{}",
+            text.list_at,
+            color_item(suggested.net(), rub, report.slots),
+            lang::fill(
+                text.after_tax,
+                &[
+                    &color_item(suggested.price, rub, report.slots),
+                    &(suggested.fee / 1000)
+                ]
+            ),
+        );
+    }
+
+    if report.flea_status == FleaStatus::Open {
+        let sparkline = trend::sparkline(recent);
+        println!(
+            "{}\t{} {}  {} {}{}",
+            text.trend,
+            text.avg_24h,
+            color_change(report.diff24h),
+            text.avg_7d,
+            color_change(report.diff7days),
+            if sparkline.is_empty() {
+                String::new()
+            } else {
+                format!("  {}", sparkline.bright_blue())
+            },
+        );
+    }
+
+    if let Some(stats) = stats.filter(|_| report.flea_status == FleaStatus::Open) {
+        let rub = Currency::Rouble;
+        println!(
+            "{}d\t{}",
+            STATS_WINDOW.num_days(),
+            lang::fill(
+                text.stats,
+                &[
+                    &color_item(stats.min, rub, report.slots),
+                    &color_item(stats.median, rub, report.slots),
+                    &color_item(stats.max, rub, report.slots),
+                    &stats.count,
+                ]
+            ),
+        );
+    }
+
+    if let Some(verdict) = sell::recommend(report, stats.as_ref()) {
+        let margin = match (verdict.margin(), &verdict.runner_up) {
+            (Some(margin), Some((venue, _))) => lang::fill(
+                text.over,
+                &[
+                    &color_currency(margin, Currency::Rouble),
+                    &venue.label(text),
+                ],
+            ),
+            _ => String::new(),
+        };
+        let venue = verdict.venue.label(text).bold();
+        println!("{}{margin}", lang::fill(text.sell_to, &[&venue]));
+        if let Some(percent) = verdict.under_median {
+            let percent = format!("{:.0}", -percent);
+            println!(
+                "{}",
+                lang::fill(text.hold, &[&percent, &STATS_WINDOW.num_days()]).yellow()
+            );
+        }
+    }
+
+    for value in uses {
+        let label = match value.recipe.kind {
+            recipes::Kind::Barter { .. } => text.barter,
+            recipes::Kind::Craft { .. } => text.craft,
+        };
+        let outputs: Vec<String> = value
+            .recipe
+            .outputs
+            .iter()
+            .map(|part| format!("{}x {}", part.count, part.item))
+            .collect();
+        println!(
+            "{label}\t{}",
+            lang::fill(
+                text.each_at,
+                &[
+                    &color_item(value.rubles, Currency::Rouble, report.slots),
+                    &value.recipe.kind,
+                    &outputs.join(", "),
+                ]
+            ),
+        );
+    }
+    let sale = recipes::sale_value(report);
+    if let (Some(best), Some(sale)) = (uses.first(), sale) {
+        if best.rubles > sale {
+            let more = format_value(best.rubles - sale);
+            println!(
+                "{}",
+                lang::fill(text.trade_in, &[&more, &best.recipe.kind]).yellow()
+            );
+        }
     }
 }
 
-fn color_currency(value: i64, cur_type: &str) -> ColoredString {
-    use num_format::{Locale, ToFormattedString};
-    let value_str = format!("{}", value.to_formatted_string(&Locale::en));
-    let rb_price = ruble_value(value, cur_type);
+fn ruble_value(value: i64, currency: Currency) -> i64 {
+    currency::rates().in_rubles(value, currency)
+}
 
-    match rb_price {
+/// Thousands-separated value, without a currency sign
+fn format_value(value: i64) -> String {
+    use num_format::ToFormattedString;
+    let config = config();
+    let locale = config
+        .number_locale
+        .map_or_else(|| config.language.number_locale(), |locale| locale.0);
+    value.to_formatted_string(&locale)
+}
 
-        x if x <= 2500   => value_str.white(),
-        x if x <= 5000   => value_str.white(),
-        x if x <= 10000  => value_str.blue(),
-        x if x <= 25000  => value_str.cyan(),
-        x if x <= 50000  => value_str.magenta(),
-        x if x <= 100000 => value_str.green(),
-        x if x <= 200000 => value_str.yellow(),
-        x if x <= 300000 => value_str.red(),
-        x if x <= 500000 => value_str.bright_red(),
-        _ => value_str.black().on_white(),
+/// Text color and optional background for an item of `slots` worth `rb_price` rubles
+fn tier_color(rb_price: i64, slots: i64) -> (Color, Option<Color>) {
+    let config = config();
+    let tier = config.tiers.tier(config.tier_basis.value(rb_price, slots));
+    (tier.color.0, tier.background.map(|bg| bg.0))
+}
+
+fn color_currency(value: i64, currency: Currency) -> ColoredString {
+    color_item(value, currency, 1)
+}
+
+/// Colored by what a whole item of `slots` is worth, per slot when the config says so
+fn color_item(value: i64, currency: Currency, slots: i64) -> ColoredString {
+    let (fg, bg) = tier_color(ruble_value(value, currency), slots);
+    let value_str = format_value(value).color(fg);
+
+    match bg {
+        Some(bg) => value_str.on_color(bg),
+        None => value_str,
     }
 }
 
 fn print_color_table() {
-    println!("     Rubles         Dollars           Euros");
-    for x in [
-        0, 1000, 2000, 3000, 5000, 7500, 10000, 15000, 25000, 50000, 75000, 100000, 125000, 150000,
-        175000, 200000, 250000, 400000, 600000, 1000000, 2000000
-    ] {
+    let rates = currency::rates();
+    let tiers = &config().tiers;
+    println!("Tiers by {} price", config().tier_basis.describe());
+    println!("     Rubles         Dollars           Euros   Tier");
+    // the top of every tier, and one past the last threshold
+    let mut values: Vec<i64> = tiers.tiers().iter().filter_map(|t| t.up_to).collect();
+    if let Some(&last) = values.last() {
+        values.push(last * 2);
+    }
+    for x in values {
         println!(
-            "{:>10}₽\t{:>10}$\t{:>10}€",
-            color_currency(x, &"₽"),
-            color_currency(x / 142, &"$"),
-            color_currency(x / 160, &"€"),
+            "{:>10}₽\t{:>10}$\t{:>10}€   {}",
+            color_currency(x, Currency::Rouble),
+            color_currency(rates.in_currency(x, Currency::Dollar), Currency::Dollar),
+            color_currency(rates.in_currency(x, Currency::Euro), Currency::Euro),
+            tiers.name(x),
         )
     }
 
     println!("Item Examples");
-    println!("");
-    print_item(&serde_json::from_str(r#"{"uid":"d43e286e-2165-40f3-a036-63cfca09b4d7","name":"Bottle of water (0.6L)","bannedOnFlea":false,"haveMarketData":true,"tags":["Provisions","Drinks"],"shortName":"Water","price":12777,"basePrice":2450,"avg24hPrice":12474,"avg7daysPrice":11861,"traderName":"Therapist","traderPrice":1544,"traderPriceCur":"₽","traderPriceRub":1544,"updated":"2024-01-07T23:54:16.662Z","slots":2,"diff24h":2.43,"diff7days":7.72,"icon":"https://cdn.tarkov-market.app/images/items/0.6L_water_bottle_sm.png?r=1695114999931","link":"https://tarkov-market.com/item/0.6L_water_bottle","wikiLink":"https://escapefromtarkov.fandom.com/wiki/Bottle_of_water_(0.6L)","img":"https://cdn.tarkov-market.app/images/items/0.6L_water_bottle_sm.png?r=1695114999931","imgBig":"https://cdn.tarkov-market.app/images/items/0.6L_water_bottle_lg.png?r=1695114999931","bsgId":"5448fee04bdc2dbc018b4567","isFunctional":true,"reference":"https://www.patreon.com/tarkov_market"}"#).unwrap());
-    println!("");
-    print_item(&serde_json::from_str(r#"{"uid":"04b8f060-9afd-424d-812b-e9b1c00a407f","name":"Dogtag case","bannedOnFlea":false,"haveMarketData":true,"tags":["Containers"],"shortName":"Dogtags","price":311111,"basePrice":310000,"avg24hPrice":316365,"avg7daysPrice":327732,"traderName":"Therapist","traderPrice":195300,"traderPriceCur":"₽","traderPriceRub":195300,"updated":"2024-01-08T07:09:31.298Z","slots":1,"diff24h":-1.66,"diff7days":-5.07,"icon":"https://cdn.tarkov-market.app/images/items/Dogtag_case_sm.png?r=1695114338851","link":"https://tarkov-market.com/item/Dogtag_case","wikiLink":"https://escapefromtarkov.fandom.com/wiki/Dogtag_case","img":"https://cdn.tarkov-market.app/images/items/Dogtag_case_sm.png?r=1695114338851","imgBig":"https://cdn.tarkov-market.app/images/items/Dogtag_case_lg.png?r=1695114338851","bsgId":"5c093e3486f77430cb02e593","isFunctional":true,"reference":"https://www.patreon.com/tarkov_market"}"#).unwrap());
-    println!("");
-    print_item(&serde_json::from_str(r#"{"uid":"b02f4698-f57d-44e6-8ccb-e786a526a456","name":"Golden rooster figurine","bannedOnFlea":false,"haveMarketData":true,"tags":["Barter"],"shortName":"Rooster","price":60000,"basePrice":90566,"avg24hPrice":65197,"avg7daysPrice":66121,"traderName":"Therapist","traderPrice":57057,"traderPriceCur":"₽","traderPriceRub":57057,"updated":"2024-01-08T05:57:40.907Z","slots":4,"diff24h":-7.97,"diff7days":-9.26,"icon":"https://cdn.tarkov-market.app/images/items/Golden_rooster_sm.png?r=1695112913793","link":"https://tarkov-market.com/item/Golden_rooster","wikiLink":"https://escapefromtarkov.fandom.com/wiki/Golden_rooster","img":"https://cdn.tarkov-market.app/images/items/Golden_rooster_sm.png?r=1695112913793","imgBig":"https://cdn.tarkov-market.app/images/items/Golden_rooster_lg.png?r=1695112913793","bsgId":"5bc9bc53d4351e00367fbcee","isFunctional":true,"reference":"https://www.patreon.com/tarkov_market"}"#).unwrap());
-    println!("");
-    print_item(&serde_json::from_str(r#"{"uid":"a7f7568c-61fb-437e-9f71-06e58aae26ba","name":"LEDX Skin Transilluminator","bannedOnFlea":false,"haveMarketData":true,"tags":["Barter"],"shortName":"LEDX","price":704000,"basePrice":970000,"avg24hPrice":704086,"avg7daysPrice":684052,"traderName":"Therapist","traderPrice":611100,"traderPriceCur":"₽","traderPriceRub":611100,"updated":"2024-01-08T06:58:05.026Z","slots":1,"diff24h":-0.01,"diff7days":2.92,"icon":"https://cdn.tarkov-market.app/images/items/ledx_skin_transilluminator_sm.png?r=1695112498138","link":"https://tarkov-market.com/item/ledx_skin_transilluminator","wikiLink":"https://escapefromtarkov.fandom.com/wiki/LEDX_Skin_Transilluminator","img":"https://cdn.tarkov-market.app/images/items/ledx_skin_transilluminator_sm.png?r=1695112498138","imgBig":"https://cdn.tarkov-market.app/images/items/ledx_skin_transilluminator_lg.png?r=1695112498138","bsgId":"5c0530ee86f774697952d952","isFunctional":true,"reference":"https://www.patreon.com/tarkov_market"}"#).unwrap());
-    println!("");
-    print_item(&serde_json::from_str(r#"{"uid":"2df5e427-5bee-4524-a204-3e4bf157a111","name":"Team Wendy EXFIL Ballistic Helmet (Coyote Brown)","bannedOnFlea":true,"haveMarketData":false,"tags":["Gear","Helmets"],"shortName":"EXFIL","price":131200,"basePrice":5068,"avg24hPrice":131200,"avg7daysPrice":131200,"traderName":"Ragman","traderPrice":3142,"traderPriceCur":"₽","traderPriceRub":3142,"updated":"2021-12-10T09:36:23.647Z","slots":4,"diff24h":0,"diff7days":0,"icon":"https://cdn.tarkov-market.app/images/items/2df5e427-5bee-4524-a204-3e4bf157a111_sm.png?r=1","link":"https://tarkov-market.com/item/Team_Wendy_EXFIL_Ballistic_Helmet_Coyote","wikiLink":"https://escapefromtarkov.fandom.com/wiki/Team_Wendy_EXFIL_Ballistic_Helmet","img":"https://cdn.tarkov-market.app/images/items/2df5e427-5bee-4524-a204-3e4bf157a111_sm.png?r=1","imgBig":"https://cdn.tarkov-market.app/images/items/2df5e427-5bee-4524-a204-3e4bf157a111_lg.png?r=1","bsgId":"5e01ef6886f77445f643baa4","isFunctional":true,"reference":"https://www.patreon.com/tarkov_market"}"#).unwrap());
+    for item in apis::market::sample_items() {
+        println!();
+        print_item(&ItemReport::new(&item), &[], None, &[]);
+    }
 }
@@ -0,0 +1,224 @@
+//! A saved corpus of tooltip crops and what each one says, for comparing how well OCR reads
+//! them after each preprocessing chain.
+//!
+//! With `ocr_corpus` set in the config, every lookup saves its crop as a png there and adds a
+//! line to `labels.tsv`: a `?`, the file name, a tab, and the item it was matched to. That
+//! name is only OCR's own guess, so scoring OCR against it would flatter it. The `?` marks the
+//! line unverified and the evaluation skips it until someone checks the name and removes it.
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use image::RgbImage;
+
+pub const LABELS_FILE: &str = "labels.tsv";
+
+/// Starts a line nobody has checked yet
+pub const UNVERIFIED: char = '?';
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub path: PathBuf,
+    /// the item name the crop shows
+    pub expected: String,
+}
+
+/// The checked samples in a corpus, and how many are still waiting to be checked
+#[derive(Debug, Clone, PartialEq)]
+pub struct Corpus {
+    pub samples: Vec<Sample>,
+    pub unverified: usize,
+}
+
+/// Every verified sample listed in `dir/labels.tsv`. Blank lines and `#` comments are skipped.
+pub fn load_corpus(dir: impl AsRef<Path>) -> io::Result<Corpus> {
+    let dir = dir.as_ref();
+    let text = fs::read_to_string(dir.join(LABELS_FILE))?;
+    let mut samples = vec![];
+    let mut unverified = 0;
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with(UNVERIFIED) {
+            unverified += 1;
+            continue;
+        }
+        let Some((file, expected)) = line.split_once('\t') else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{LABELS_FILE} line {}: expected a file and a name", i + 1),
+            ));
+        };
+        samples.push(Sample {
+            path: dir.join(file),
+            expected: expected.trim().to_owned(),
+        });
+    }
+    Ok(Corpus {
+        samples,
+        unverified,
+    })
+}
+
+/// Keep `image` as an unverified sample of `expected`, named after the time it was taken
+pub fn save_sample(dir: impl AsRef<Path>, image: &RgbImage, expected: &str) -> io::Result<()> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    let file = format!("{}.png", Utc::now().format("%Y%m%d-%H%M%S%.3f"));
+    image.save(dir.join(&file)).map_err(io::Error::other)?;
+
+    let mut labels = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(LABELS_FILE))?;
+    writeln!(labels, "{UNVERIFIED}{file}\t{expected}")
+}
+
+/// How one chain did over the corpus
+#[derive(Debug, Clone, PartialEq)]
+pub struct Score {
+    pub samples: usize,
+    /// the raw OCR text was exactly the name
+    pub exact: usize,
+    /// the closest item name was the right one
+    pub matched: usize,
+    /// 1 minus the edit distance over the name's length, averaged
+    pub char_accuracy: f64,
+}
+
+/// One read of one sample: what it should say, what OCR read, and what that matched
+pub struct Read<'a> {
+    pub expected: &'a str,
+    pub ocr_text: &'a str,
+    pub matched: Option<&'a str>,
+}
+
+pub fn score<'a>(reads: impl IntoIterator<Item = Read<'a>>) -> Score {
+    let mut score = Score {
+        samples: 0,
+        exact: 0,
+        matched: 0,
+        char_accuracy: 0.0,
+    };
+    let mut accuracy = 0.0;
+    for read in reads {
+        score.samples += 1;
+        score.exact += usize::from(read.ocr_text == read.expected);
+        score.matched += usize::from(read.matched == Some(read.expected));
+        let len = read.expected.chars().count().max(1);
+        let errors = edit_distance(read.ocr_text, read.expected).min(len);
+        accuracy += 1.0 - errors as f64 / len as f64;
+    }
+    if score.samples > 0 {
+        score.char_accuracy = accuracy / score.samples as f64;
+    }
+    score
+}
+
+/// Levenshtein distance, in chars
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitute.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn distances() {
+        assert_eq!(edit_distance("Salewa", "Salewa"), 0);
+        assert_eq!(edit_distance("Sa1ewa", "Salewa"), 1);
+        assert_eq!(edit_distance("", "LEDX"), 4);
+        assert_eq!(edit_distance("Бутылка", "Бутылки"), 1);
+    }
+
+    #[test]
+    fn scoring() {
+        let score = score([
+            Read {
+                expected: "Salewa first aid kit",
+                ocr_text: "Salewa first aid kit",
+                matched: Some("Salewa first aid kit"),
+            },
+            Read {
+                expected: "LEDX",
+                ocr_text: "LFDX",
+                matched: Some("LEDX"),
+            },
+            Read {
+                expected: "Golden rooster",
+                ocr_text: "",
+                matched: None,
+            },
+        ]);
+        assert_eq!((score.samples, score.exact, score.matched), (3, 1, 2));
+        assert!((score.char_accuracy - 1.75 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn corpus_roundtrip() {
+        let temp = TempPath::new("corpus");
+        let dir = temp.path();
+        save_sample(dir, &RgbImage::new(4, 2), "Bottle of water (0.6L)").unwrap();
+        save_sample(dir, &RgbImage::new(4, 2), "Bottle of watre").unwrap();
+        let corpus = load_corpus(dir).unwrap();
+        assert_eq!((corpus.samples.len(), corpus.unverified), (0, 2));
+
+        // checking the first by hand
+        let labels = fs::read_to_string(dir.join(LABELS_FILE)).unwrap();
+        let checked = format!("# checked by hand\n\n{}", &labels[1..]);
+        fs::write(dir.join(LABELS_FILE), checked).unwrap();
+
+        let corpus = load_corpus(dir).unwrap();
+        assert_eq!(corpus.unverified, 1);
+        let samples = corpus.samples;
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].expected, "Bottle of water (0.6L)");
+        assert_eq!(image::open(&samples[0].path).unwrap().width(), 4);
+
+        fs::write(dir.join(LABELS_FILE), "no tab here\n").unwrap();
+        assert!(load_corpus(dir).is_err());
+    }
+
+    #[test]
+    fn samples_saved_by_a_lookup_score_their_own_reading() {
+        let temp = TempPath::new("corpus_lookup");
+        let dir = temp.path();
+
+        // what a lookup does with a clean read
+        let ocr_text = "Bottle of water (0.6L)";
+        let (text, _) = crate::WORDS
+            .read()
+            .unwrap()
+            .get_closest_scored(ocr_text)
+            .unwrap();
+        save_sample(dir, &RgbImage::new(4, 2), &text).unwrap();
+
+        let labels = fs::read_to_string(dir.join(LABELS_FILE)).unwrap();
+        fs::write(dir.join(LABELS_FILE), &labels[1..]).unwrap();
+        let samples = load_corpus(dir).unwrap().samples;
+        assert_eq!(samples[0].expected, ocr_text);
+
+        let score = score([Read {
+            expected: &samples[0].expected,
+            ocr_text,
+            matched: Some(&text),
+        }]);
+        assert_eq!((score.exact, score.matched), (1, 1));
+    }
+}
@@ -0,0 +1,443 @@
+//! Popup that shows the lookup next to the cursor. Drawing is plain pixel pushing into an
+//! `image` buffer so it can be checked without a display; only `show` needs a real window.
+use std::time::Duration;
+
+use colored::Color;
+use font8x8::{UnicodeFonts, BASIC_FONTS, LATIN_FONTS};
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    config,
+    currency::Currency,
+    details::Details,
+    format_value,
+    lang::fill,
+    report::{FleaStatus, ItemReport},
+    ruble_value,
+    screenshot::CursorPos,
+    tier_color,
+};
+
+const SCALE: u32 = 2;
+const GLYPH: u32 = 8 * SCALE;
+const LINE_HEIGHT: u32 = GLYPH + 4;
+const PADDING: u32 = 8;
+/// Gap between two items when a lookup matched several
+const ITEM_GAP: u32 = 10;
+
+const BACKGROUND: Rgba<u8> = Rgba([20, 20, 20, 235]);
+/// Same grey as the in-game inspect box border
+const BORDER: Rgba<u8> = Rgba([0x60, 0x5d, 0x58, 255]);
+const TEXT: [u8; 3] = [220, 220, 220];
+const DIM: [u8; 3] = [140, 140, 140];
+
+/// font8x8 has no currency signs, so the two we need are drawn by hand
+const RUBLE_GLYPH: [u8; 8] = [0x1E, 0x22, 0x22, 0x1F, 0x02, 0x0F, 0x02, 0x00];
+const EURO_GLYPH: [u8; 8] = [0x3C, 0x42, 0x0F, 0x02, 0x0F, 0x42, 0x3C, 0x00];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Span {
+    text: String,
+    fg: [u8; 3],
+    bg: Option<[u8; 3]>,
+}
+
+impl Span {
+    fn plain(text: impl Into<String>, fg: [u8; 3]) -> Self {
+        Self {
+            text: text.into(),
+            fg,
+            bg: None,
+        }
+    }
+
+    /// A money value colored by its tier, followed by its currency sign
+    fn money(value: i64, currency: Currency) -> Vec<Self> {
+        Self::item_money(value, currency, 1)
+    }
+
+    /// The price of a whole item of `slots`, tiered per slot when the config says so
+    fn item_money(value: i64, currency: Currency, slots: i64) -> Vec<Self> {
+        let (fg, bg) = tier_color(ruble_value(value, currency), slots);
+        vec![
+            Self {
+                text: format_value(value),
+                fg: rgb(fg),
+                bg: bg.map(rgb),
+            },
+            Self::plain(currency.sign(), TEXT),
+        ]
+    }
+}
+
+type Line = Vec<Span>;
+
+fn rgb(color: Color) -> [u8; 3] {
+    match color {
+        Color::Black => [0, 0, 0],
+        Color::Blue => [80, 130, 255],
+        Color::Cyan => [0, 200, 220],
+        Color::Magenta => [220, 80, 220],
+        Color::Green => [80, 220, 80],
+        Color::Yellow => [240, 220, 60],
+        Color::Red => [220, 60, 60],
+        Color::BrightRed => [255, 110, 110],
+        Color::BrightBlack => [128, 128, 128],
+        Color::BrightGreen => [140, 255, 140],
+        Color::BrightYellow => [255, 250, 130],
+        Color::BrightBlue => [130, 170, 255],
+        Color::BrightMagenta => [255, 130, 255],
+        Color::BrightCyan => [130, 240, 255],
+        Color::TrueColor { r, g, b } => [r, g, b],
+        _ => [255, 255, 255],
+    }
+}
+
+fn per_slot_spans(report: &ItemReport, value: i64, currency: Currency) -> Vec<Span> {
+    match report.per_slot(value) {
+        Some(slot_value) => {
+            let mut spans = vec![Span::plain(" (", DIM)];
+            spans.extend(Span::money(slot_value, currency));
+            spans.push(Span::plain(format!(" x {})", report.slots), DIM));
+            spans
+        }
+        None => vec![],
+    }
+}
+
+fn lines(report: &ItemReport) -> Vec<Line> {
+    let text = config().language.strings();
+    let mut lines = vec![vec![
+        Span::plain(&report.name, rgb(Color::Red)),
+        Span::plain(format!(" ({})", report.short_name), DIM),
+    ]];
+
+    for requirement in &report.needed {
+        lines.push(vec![
+            Span::plain(format!("{:<12}", text.needed), TEXT),
+            Span::plain(
+                fill(
+                    text.needed_for,
+                    &[&requirement.amount(), &requirement.needed_for],
+                ),
+                rgb(Color::Yellow),
+            ),
+        ]);
+    }
+
+    if let Some(details) = &report.details {
+        let mut line = vec![
+            Span::plain(format!("{:<12}", details.label(text)), TEXT),
+            Span::plain(details.summary(text, report.slots), rgb(Color::Cyan)),
+        ];
+        if let Details::Container(container) = details {
+            if let Some(price) = container.per_slot_gained(report.unit_price(), report.slots) {
+                line.push(Span::plain(" ", TEXT));
+                line.extend(Span::money(price, Currency::Rouble));
+                line.push(Span::plain("/slot", DIM));
+            }
+        }
+        lines.push(line);
+    }
+
+    let trader = &report.trader;
+    let mut line = vec![Span::plain(format!("{:<12}", trader.name), TEXT)];
+    line.extend(Span::item_money(
+        trader.price,
+        trader.currency,
+        report.slots,
+    ));
+    line.extend(per_slot_spans(report, trader.price, trader.currency));
+    lines.push(line);
+
+    if report.flea_status != FleaStatus::Open {
+        lines.push(vec![
+            Span::plain(format!("{:<12}", text.flea), TEXT),
+            Span::plain(report.flea_status.label(text), rgb(Color::Yellow)),
+        ]);
+    }
+
+    for offer in &report.flea {
+        let mut line = vec![Span::plain(
+            format!(
+                "{:<12}",
+                format!("{} {}", offer.basis.label(text), text.flea)
+            ),
+            TEXT,
+        )];
+        line.extend(Span::item_money(
+            offer.net(),
+            Currency::Rouble,
+            report.slots,
+        ));
+        line.extend(per_slot_spans(report, offer.net(), Currency::Rouble));
+        line.push(Span::plain(format!(" -{}k tax", offer.tax / 1000), DIM));
+        lines.push(line);
+    }
+
+    lines
+}
+
+fn glyph(c: char) -> [u8; 8] {
+    match c {
+        '₽' => RUBLE_GLYPH,
+        '€' => EURO_GLYPH,
+        // the spaces some locales group digits with
+        c if c.is_whitespace() => BASIC_FONTS.get(' ').unwrap(),
+        // latin-1 for the umlauts in the german labels
+        c => BASIC_FONTS
+            .get(c)
+            .or_else(|| LATIN_FONTS.get(c))
+            .or_else(|| BASIC_FONTS.get('?'))
+            .unwrap(),
+    }
+}
+
+fn draw_line(img: &mut RgbaImage, line: &Line, x: u32, y: u32) {
+    let mut x = x;
+    for span in line {
+        for c in span.text.chars() {
+            if let Some([r, g, b]) = span.bg {
+                for dy in 0..LINE_HEIGHT {
+                    for dx in 0..GLYPH {
+                        img.put_pixel(x + dx, y + dy - 2, Rgba([r, g, b, 255]));
+                    }
+                }
+            }
+
+            let [r, g, b] = span.fg;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..8 {
+                    if bits & (1 << col) == 0 {
+                        continue;
+                    }
+                    for sy in 0..SCALE {
+                        for sx in 0..SCALE {
+                            let px = x + col * SCALE + sx;
+                            let py = y + row as u32 * SCALE + sy;
+                            img.put_pixel(px, py, Rgba([r, g, b, 255]));
+                        }
+                    }
+                }
+            }
+
+            x += GLYPH;
+        }
+    }
+}
+
+/// Draw the popup for every matched item, stacked top to bottom
+pub fn render(reports: &[ItemReport]) -> RgbaImage {
+    let blocks: Vec<Vec<Line>> = reports.iter().map(lines).collect();
+
+    let widest = blocks
+        .iter()
+        .flatten()
+        .map(|line| line.iter().map(|s| s.text.chars().count()).sum::<usize>())
+        .max()
+        .unwrap_or(0) as u32;
+    let line_count = blocks.iter().map(Vec::len).sum::<usize>() as u32;
+    let gaps = blocks.len().saturating_sub(1) as u32;
+
+    let width = widest * GLYPH + PADDING * 2;
+    let height = line_count * LINE_HEIGHT + gaps * ITEM_GAP + PADDING * 2;
+
+    let mut img = RgbaImage::from_pixel(width, height, BACKGROUND);
+    for x in 0..width {
+        img.put_pixel(x, 0, BORDER);
+        img.put_pixel(x, height - 1, BORDER);
+    }
+    for y in 0..height {
+        img.put_pixel(0, y, BORDER);
+        img.put_pixel(width - 1, y, BORDER);
+    }
+
+    let mut y = PADDING;
+    for block in &blocks {
+        for line in block {
+            draw_line(&mut img, line, PADDING, y + 2);
+            y += LINE_HEIGHT;
+        }
+        y += ITEM_GAP;
+    }
+
+    img
+}
+
+/// Pop the results up next to the cursor for `duration`. A newer popup replaces the old one.
+#[cfg(feature = "overlay")]
+pub fn show(reports: &[ItemReport], at: CursorPos, duration: Duration) {
+    if reports.is_empty() {
+        return;
+    }
+
+    let popup = window::Popup {
+        image: render(reports),
+        x: at.x as isize + 16,
+        y: at.y as isize + 16,
+        until: std::time::Instant::now() + duration,
+    };
+
+    // the window thread only goes away if it panicked, nothing useful to do about that here
+    let _ = window::POPUPS.lock().unwrap().send(popup);
+}
+
+#[cfg(not(feature = "overlay"))]
+pub fn show(_reports: &[ItemReport], _at: CursorPos, _duration: Duration) {}
+
+#[cfg(feature = "overlay")]
+mod window {
+    use std::{
+        sync::{
+            mpsc::{self, Receiver, Sender},
+            Mutex,
+        },
+        time::Instant,
+    };
+
+    use image::RgbaImage;
+    use minifb::{Window, WindowOptions};
+    use once_cell::sync::Lazy;
+
+    pub struct Popup {
+        pub image: RgbaImage,
+        pub x: isize,
+        pub y: isize,
+        pub until: Instant,
+    }
+
+    /// minifb windows have to be pumped by the thread that made them, so one thread owns it
+    pub static POPUPS: Lazy<Mutex<Sender<Popup>>> = Lazy::new(|| {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || run(rx));
+        Mutex::new(tx)
+    });
+
+    fn run(rx: Receiver<Popup>) {
+        let mut next = rx.recv().ok();
+        while let Some(popup) = next.take() {
+            next = display(popup, &rx).or_else(|| rx.recv().ok());
+        }
+    }
+
+    /// Show one popup until it times out. Returns early with the replacement if a newer one
+    /// comes in.
+    fn display(popup: Popup, rx: &Receiver<Popup>) -> Option<Popup> {
+        let (w, h) = (popup.image.width() as usize, popup.image.height() as usize);
+        // minifb wants 0RGB
+        let buffer: Vec<u32> = popup
+            .image
+            .pixels()
+            .map(|p| u32::from_be_bytes([0, p[0], p[1], p[2]]))
+            .collect();
+
+        let options = WindowOptions {
+            borderless: true,
+            title: false,
+            topmost: true,
+            ..Default::default()
+        };
+        let mut window = match Window::new("Tarkov price", w, h, options) {
+            Ok(window) => window,
+            Err(e) => {
+                println!("Could not open the overlay window: {}", e);
+                return None;
+            }
+        };
+        window.set_position(popup.x, popup.y);
+        window.set_target_fps(30);
+        make_click_through(&window);
+
+        while window.is_open() && Instant::now() < popup.until {
+            if let Ok(newer) = rx.try_recv() {
+                return Some(newer);
+            }
+            if window.update_with_buffer(&buffer, w, h).is_err() {
+                break;
+            }
+        }
+
+        None
+    }
+
+    #[cfg(target_os = "windows")]
+    fn make_click_through(window: &Window) {
+        use std::os::raw::{c_int, c_void};
+
+        const GWL_EXSTYLE: c_int = -20;
+        const WS_EX_LAYERED: isize = 0x0008_0000;
+        const WS_EX_TRANSPARENT: isize = 0x0000_0020;
+        const LWA_ALPHA: u32 = 0x2;
+
+        #[link(name = "user32")]
+        extern "system" {
+            fn GetWindowLongPtrW(hwnd: *mut c_void, index: c_int) -> isize;
+            fn SetWindowLongPtrW(hwnd: *mut c_void, index: c_int, value: isize) -> isize;
+            fn SetLayeredWindowAttributes(
+                hwnd: *mut c_void,
+                key: u32,
+                alpha: u8,
+                flags: u32,
+            ) -> c_int;
+        }
+
+        let hwnd = window.get_window_handle();
+        // transparent to clicks so it never steals focus from the game
+        unsafe {
+            let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+            SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style | WS_EX_LAYERED | WS_EX_TRANSPARENT);
+            SetLayeredWindowAttributes(hwnd, 0, 235, LWA_ALPHA);
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn make_click_through(_window: &Window) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::market::sample_items;
+
+    const SNAPSHOT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots");
+
+    /// Compare against the saved png. Run with `UPDATE_SNAPSHOTS=1` to accept a new layout.
+    fn assert_snapshot(name: &str, img: &RgbaImage) {
+        let path = format!("{SNAPSHOT_DIR}/{name}.png");
+
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(SNAPSHOT_DIR).unwrap();
+            img.save(&path).unwrap();
+            return;
+        }
+
+        let expected = image::open(&path)
+            .unwrap_or_else(|e| panic!("{path}: {e}, run with UPDATE_SNAPSHOTS=1"))
+            .to_rgba8();
+        assert!(expected == *img, "overlay render differs from {path}");
+    }
+
+    #[test]
+    fn layout_lines() {
+        let water = ItemReport::new(&sample_items()[0]);
+        let text: Vec<String> = lines(&water)
+            .iter()
+            .map(|line| line.iter().map(|s| s.text.as_str()).collect())
+            .collect();
+
+        assert_eq!(text[0], "Bottle of water (0.6L) (Water)");
+        assert_eq!(text[1], "Therapist   1,544₽ (772₽ x 2)");
+        assert!(text[2].starts_with("Lowest Flea"));
+        assert_eq!(text.len(), 5);
+    }
+
+    #[test]
+    fn render_snapshots() {
+        let items = sample_items();
+        let water = ItemReport::new(&items[0]);
+        assert_snapshot("water", &render(&[water]));
+
+        let all: Vec<_> = items.iter().map(ItemReport::new).collect();
+        assert_snapshot("all_samples", &render(&all));
+    }
+}
@@ -0,0 +1,267 @@
+//! Cleanup applied to the tooltip crop before OCR.
+//!
+//! Tooltip text is small, light grey, and sits on a dark gradient, which the recognition
+//! model reads worse than plain print. The config can list steps to run on the crop first,
+//! in order. With none the crop goes to OCR untouched.
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, GrayImage, Luma, RgbImage,
+};
+use serde::Deserialize;
+
+/// `{"step": "upscale", "factor": 3}`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    /// make the crop `factor` times bigger, up to 8, the model reads larger glyphs better
+    Upscale { factor: u32 },
+    /// spread the brightness out to the full range, ignoring the darkest and brightest
+    /// `clip_percent` of pixels
+    Stretch {
+        #[serde(default = "default_clip")]
+        clip_percent: f32,
+    },
+    /// white where a pixel is at least `offset` brighter than the mean of the `window`
+    /// around it, black everywhere else
+    Threshold {
+        #[serde(default = "default_window")]
+        window: u32,
+        #[serde(default = "default_offset")]
+        offset: i32,
+    },
+    /// subtract the tooltip's gradient, taken as the median of each column
+    RemoveBackground,
+    /// dark text on light instead of the other way round
+    Invert,
+}
+
+/// Past this the crop is mostly interpolation, and a typo'd factor could run out of memory
+const MAX_UPSCALE: u32 = 8;
+
+fn default_clip() -> f32 {
+    1.0
+}
+
+fn default_window() -> u32 {
+    15
+}
+
+fn default_offset() -> i32 {
+    10
+}
+
+/// A few chains worth comparing, for when the evaluation isn't given its own
+pub fn presets() -> Vec<(String, Vec<Step>)> {
+    let upscale = Step::Upscale { factor: 3 };
+    let stretch = Step::Stretch {
+        clip_percent: default_clip(),
+    };
+    let threshold = Step::Threshold {
+        window: default_window() * 3,
+        offset: default_offset(),
+    };
+    vec![
+        ("none".to_owned(), vec![]),
+        ("upscale".to_owned(), vec![upscale.clone()]),
+        ("stretch".to_owned(), vec![upscale.clone(), stretch.clone()]),
+        (
+            "background".to_owned(),
+            vec![upscale.clone(), Step::RemoveBackground, stretch.clone()],
+        ),
+        ("threshold".to_owned(), vec![upscale, stretch, threshold]),
+    ]
+}
+
+/// `image` after every step of `chain`. The steps work in greyscale, which is what the model
+/// converts to anyway.
+pub fn apply(image: &RgbImage, chain: &[Step]) -> RgbImage {
+    if chain.is_empty() {
+        return image.clone();
+    }
+    let mut gray = imageops::grayscale(image);
+    for step in chain {
+        gray = match *step {
+            Step::Upscale { factor } => upscale(&gray, factor),
+            Step::Stretch { clip_percent } => stretch(gray, clip_percent),
+            Step::Threshold { window, offset } => threshold(&gray, window, offset),
+            Step::RemoveBackground => remove_background(gray),
+            Step::Invert => {
+                imageops::invert(&mut gray);
+                gray
+            }
+        };
+    }
+    DynamicImage::ImageLuma8(gray).into_rgb8()
+}
+
+fn upscale(image: &GrayImage, factor: u32) -> GrayImage {
+    let factor = factor.clamp(1, MAX_UPSCALE);
+    imageops::resize(
+        image,
+        image.width() * factor,
+        image.height() * factor,
+        FilterType::CatmullRom,
+    )
+}
+
+fn stretch(mut image: GrayImage, clip_percent: f32) -> GrayImage {
+    let mut histogram = [0usize; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let clip = (image.len() as f32 * clip_percent.clamp(0.0, 50.0) / 100.0) as usize;
+    let low = cutoff(&histogram, clip, 0..256);
+    let high = cutoff(&histogram, clip, (0..256).rev());
+    if high <= low {
+        return image;
+    }
+
+    for pixel in image.pixels_mut() {
+        let value = (pixel.0[0] as i32 - low) * 255 / (high - low);
+        pixel.0[0] = value.clamp(0, 255) as u8;
+    }
+    image
+}
+
+/// The first of `values` with more than `clip` pixels up to and including it
+fn cutoff(histogram: &[usize; 256], clip: usize, mut values: impl Iterator<Item = usize>) -> i32 {
+    let mut seen = 0;
+    values
+        .find(|&v| {
+            seen += histogram[v];
+            seen > clip
+        })
+        .unwrap_or(0) as i32
+}
+
+fn threshold(image: &GrayImage, window: u32, offset: i32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let (w, h) = (width as usize, height as usize);
+    // sums of everything above and left of each pixel, so any window's mean is 4 lookups
+    let mut integral = vec![0u64; (w + 1) * (h + 1)];
+    for y in 0..h {
+        let mut row = 0u64;
+        for x in 0..w {
+            row += image.get_pixel(x as u32, y as u32).0[0] as u64;
+            integral[(y + 1) * (w + 1) + x + 1] = integral[y * (w + 1) + x + 1] + row;
+        }
+    }
+
+    let half = (window.max(1) / 2) as usize;
+    GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as usize, y as usize);
+        let (x0, y0) = (x.saturating_sub(half), y.saturating_sub(half));
+        let (x1, y1) = ((x + half + 1).min(w), (y + half + 1).min(h));
+        let sum = integral[y1 * (w + 1) + x1] + integral[y0 * (w + 1) + x0]
+            - integral[y0 * (w + 1) + x1]
+            - integral[y1 * (w + 1) + x0];
+        let mean = (sum / ((x1 - x0) * (y1 - y0)) as u64) as i32;
+        let value = image.get_pixel(x as u32, y as u32).0[0] as i32;
+        Luma([if value >= mean + offset { 255 } else { 0 }])
+    })
+}
+
+/// The crop is a single line of text, so most of any column is background
+fn remove_background(mut image: GrayImage) -> GrayImage {
+    let (width, height) = image.dimensions();
+    for x in 0..width {
+        let mut column: Vec<u8> = (0..height).map(|y| image.get_pixel(x, y).0[0]).collect();
+        column.sort_unstable();
+        let background = column[column.len() / 2];
+        for y in 0..height {
+            let pixel = image.get_pixel_mut(x, y);
+            pixel.0[0] = pixel.0[0].saturating_sub(background);
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    /// Dark background getting lighter to the right, with a bright bar across the middle
+    fn tooltip() -> RgbImage {
+        RgbImage::from_fn(40, 9, |x, y| {
+            let background = 20 + x as u8;
+            if y == 4 {
+                Rgb([background + 100; 3])
+            } else {
+                Rgb([background; 3])
+            }
+        })
+    }
+
+    fn gray(image: &RgbImage, x: u32, y: u32) -> u8 {
+        image.get_pixel(x, y).0[0]
+    }
+
+    #[test]
+    fn chain_from_config() {
+        let chain: Vec<Step> = serde_json::from_str(
+            r#"[{"step": "upscale", "factor": 2}, {"step": "stretch"},
+                {"step": "threshold", "window": 31}, {"step": "remove_background"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            chain,
+            [
+                Step::Upscale { factor: 2 },
+                Step::Stretch { clip_percent: 1.0 },
+                Step::Threshold {
+                    window: 31,
+                    offset: 10
+                },
+                Step::RemoveBackground,
+            ]
+        );
+        assert!(serde_json::from_str::<Vec<Step>>(r#"[{"step": "sharpen"}]"#).is_err());
+    }
+
+    #[test]
+    fn no_steps_is_untouched() {
+        let image = tooltip();
+        assert_eq!(apply(&image, &[]), image);
+    }
+
+    #[test]
+    fn upscale_and_stretch() {
+        let image = apply(
+            &tooltip(),
+            &[
+                Step::Upscale { factor: 3 },
+                Step::Stretch { clip_percent: 0.0 },
+            ],
+        );
+        assert_eq!(image.dimensions(), (120, 27));
+        let values: Vec<u8> = image.pixels().map(|p| p.0[0]).collect();
+        assert_eq!(values.iter().min(), Some(&0));
+        assert_eq!(values.iter().max(), Some(&255));
+
+        // a factor that would overflow the dimensions is capped instead
+        let capped = apply(&tooltip(), &[Step::Upscale { factor: u32::MAX }]);
+        assert_eq!(capped.dimensions(), (40 * MAX_UPSCALE, 9 * MAX_UPSCALE));
+    }
+
+    #[test]
+    fn background_and_threshold() {
+        let flat = apply(&tooltip(), &[Step::RemoveBackground]);
+        // the gradient is gone, the bar is the same brightness all the way along
+        assert_eq!((gray(&flat, 0, 0), gray(&flat, 39, 0)), (0, 0));
+        assert_eq!((gray(&flat, 0, 4), gray(&flat, 39, 4)), (100, 100));
+
+        let binary = apply(
+            &tooltip(),
+            &[Step::Threshold {
+                window: 5,
+                offset: 10,
+            }],
+        );
+        assert!(binary.pixels().all(|p| p.0[0] == 0 || p.0[0] == 255));
+        assert_eq!((gray(&binary, 20, 4), gray(&binary, 20, 1)), (255, 0));
+
+        let inverted = apply(&binary, &[Step::Invert]);
+        assert_eq!(gray(&inverted, 20, 4), 0);
+    }
+}
@@ -0,0 +1,292 @@
+//! Price samples per item, kept in `prices.jsonl` so we can say how today's price compares
+//! to the last week.
+//!
+//! Samples come from our own lookups (at most one per item every `SAMPLE_GAP`, so mashing the
+//! hotkey doesn't skew the numbers) and from the market api's item history where the api key
+//! is allowed to ask for it.
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{apis::market::HistoryPoint, status};
+
+pub const PRICES_PATH: &str = "prices.jsonl";
+
+/// Lookups of the same item closer together than this only keep the first sample
+pub const SAMPLE_GAP: TimeDelta = TimeDelta::minutes(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SampleSource {
+    /// one of our own lookups
+    Lookup,
+    /// the market api's price history
+    Backend,
+}
+
+/// The lowest flea price of one item at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sample {
+    pub uid: String,
+    pub at: DateTime<Utc>,
+    pub price: i64,
+    pub source: SampleSource,
+}
+
+/// Min, max and median over some window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowStats {
+    pub count: usize,
+    pub min: i64,
+    pub max: i64,
+    pub median: i64,
+}
+
+impl WindowStats {
+    pub fn of(prices: &[i64]) -> Option<Self> {
+        if prices.is_empty() {
+            return None;
+        }
+        let mut sorted = prices.to_vec();
+        sorted.sort_unstable();
+        let mid = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) {
+            (sorted[mid - 1] + sorted[mid]) / 2
+        } else {
+            sorted[mid]
+        };
+
+        Some(Self {
+            count: sorted.len(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            median,
+        })
+    }
+
+    /// How far `price` is from the median, in percent
+    pub fn vs_median(&self, price: i64) -> f64 {
+        if self.median == 0 {
+            return 0.0;
+        }
+        (price - self.median) as f64 / self.median as f64 * 100.0
+    }
+}
+
+pub struct PriceStore {
+    path: PathBuf,
+    /// oldest first per item, but items are interleaved
+    samples: Vec<Sample>,
+}
+
+impl PriceStore {
+    /// A store with nothing in it yet, that will write to `path`
+    pub fn empty(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            samples: vec![],
+        }
+    }
+
+    /// Load every sample from `path`. A missing file is an empty store, and lines that don't
+    /// parse are skipped with a warning like the lookup history does.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let file = match fs::File::open(&path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::empty(path)),
+            Err(e) => return Err(e),
+        };
+
+        let mut samples = vec![];
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(sample) => samples.push(sample),
+                Err(e) => status(format!(
+                    "{}:{}: skipping bad sample: {}",
+                    path.display(),
+                    i + 1,
+                    e
+                )),
+            }
+        }
+        samples.sort_by_key(|s: &Sample| s.at);
+
+        Ok(Self { path, samples })
+    }
+
+    fn append(&mut self, new: Vec<Sample>) -> io::Result<usize> {
+        if new.is_empty() {
+            return Ok(0);
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        for sample in &new {
+            writeln!(file, "{}", serde_json::to_string(sample)?)?;
+        }
+
+        let added = new.len();
+        self.samples.extend(new);
+        self.samples.sort_by_key(|s| s.at);
+        Ok(added)
+    }
+
+    /// Keep a sample from a lookup, unless we already have one of this item from the last
+    /// `SAMPLE_GAP`. Returns whether it was kept.
+    pub fn record(&mut self, sample: Sample) -> io::Result<bool> {
+        let recent = self.samples.iter().any(|s| {
+            s.uid == sample.uid && s.source == SampleSource::Lookup && sample.at - s.at < SAMPLE_GAP
+        });
+        if recent {
+            return Ok(false);
+        }
+        Ok(self.append(vec![sample])? == 1)
+    }
+
+    /// Add the api's history for one item, skipping points we already have.
+    /// Returns how many were new.
+    pub fn merge_backend(&mut self, uid: &str, points: &[HistoryPoint]) -> io::Result<usize> {
+        let latest = self.latest(uid, SampleSource::Backend);
+        let new = points
+            .iter()
+            .filter(|p| p.price > 0 && latest.is_none_or(|latest| p.time > latest))
+            .map(|p| Sample {
+                uid: uid.to_owned(),
+                at: p.time,
+                price: p.price,
+                source: SampleSource::Backend,
+            })
+            .collect();
+        self.append(new)
+    }
+
+    /// When the newest sample of `uid` from `source` was taken
+    pub fn latest(&self, uid: &str, source: SampleSource) -> Option<DateTime<Utc>> {
+        self.samples
+            .iter()
+            .rev()
+            .find(|s| s.uid == uid && s.source == source)
+            .map(|s| s.at)
+    }
+
+    /// Every price of `uid` since `since`, oldest first, from both sources
+    pub fn prices_since(&self, uid: &str, since: DateTime<Utc>) -> Vec<i64> {
+        self.samples
+            .iter()
+            .filter(|s| s.uid == uid && s.at >= since)
+            .map(|s| s.price)
+            .collect()
+    }
+
+    /// The last `count` prices of `uid`, oldest first, from both sources
+    pub fn recent(&self, uid: &str, count: usize) -> Vec<i64> {
+        let mut prices: Vec<i64> = self
+            .samples
+            .iter()
+            .rev()
+            .filter(|s| s.uid == uid)
+            .map(|s| s.price)
+            .take(count)
+            .collect();
+        prices.reverse();
+        prices
+    }
+
+    pub fn window(&self, uid: &str, since: DateTime<Utc>) -> Option<WindowStats> {
+        WindowStats::of(&self.prices_since(uid, since))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    fn temp_store(name: &str) -> (TempPath, PriceStore) {
+        let path = TempPath::new(name);
+        let store = PriceStore::open(&path).unwrap();
+        (path, store)
+    }
+
+    fn sample(uid: &str, minutes_ago: i64, price: i64) -> Sample {
+        Sample {
+            uid: uid.to_owned(),
+            at: Utc::now() - TimeDelta::minutes(minutes_ago),
+            price,
+            source: SampleSource::Lookup,
+        }
+    }
+
+    #[test]
+    fn stats() {
+        assert_eq!(WindowStats::of(&[]), None);
+        let stats = WindowStats::of(&[300, 100, 200, 1000]).unwrap();
+        assert_eq!(
+            stats,
+            WindowStats {
+                count: 4,
+                min: 100,
+                max: 1000,
+                median: 250
+            }
+        );
+        assert_eq!(WindowStats::of(&[5, 1, 3]).unwrap().median, 3);
+        assert_eq!(stats.vs_median(200), -20.0);
+    }
+
+    #[test]
+    fn lookups_are_sampled_at_most_every_gap() {
+        let (path, mut store) = temp_store("prices_gap.jsonl");
+
+        assert!(store.record(sample("water", 30, 12000)).unwrap());
+        assert!(!store.record(sample("water", 25, 12100)).unwrap());
+        assert!(store.record(sample("water", 5, 13000)).unwrap());
+        // a different item doesn't care
+        assert!(store.record(sample("ledx", 5, 700000)).unwrap());
+
+        let reopened = PriceStore::open(&path).unwrap();
+        let since = Utc::now() - TimeDelta::hours(1);
+        assert_eq!(reopened.prices_since("water", since), [12000, 13000]);
+        assert_eq!(reopened.window("ledx", since).unwrap().count, 1);
+        assert_eq!(reopened.window("rooster", since), None);
+    }
+
+    #[test]
+    fn backend_history_merges_once() {
+        let (_path, mut store) = temp_store("prices_backend.jsonl");
+        let now = Utc::now();
+        let points: Vec<HistoryPoint> = (1..=3)
+            .map(|days| HistoryPoint {
+                price: 10000 + days * 1000,
+                avg_price: 0,
+                time: now - TimeDelta::days(days),
+            })
+            .collect();
+
+        assert_eq!(store.merge_backend("water", &points).unwrap(), 3);
+        assert_eq!(store.merge_backend("water", &points).unwrap(), 0);
+        store.record(sample("water", 0, 12500)).unwrap();
+
+        let week = store.window("water", now - TimeDelta::days(7)).unwrap();
+        assert_eq!((week.count, week.min, week.max), (4, 11000, 13000));
+        assert_eq!(week.median, 12250);
+        assert_eq!(
+            store.prices_since("water", now - TimeDelta::hours(36)),
+            [11000, 12500]
+        );
+        // the sparkline's view: the latest few, whatever their source
+        assert_eq!(store.recent("water", 3), [12000, 11000, 12500]);
+        assert_eq!(store.recent("water", 10).len(), 4);
+        assert!(store.recent("ledx", 10).is_empty());
+    }
+}
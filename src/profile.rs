@@ -0,0 +1,128 @@
+use std::{collections::HashMap, fs, io::ErrorKind};
+
+use serde::Deserialize;
+
+use crate::{
+    fees::FeeSettings,
+    requirements::{Need, Requirement},
+};
+
+/// What the player has already done, read from the working directory like the config
+pub const PROFILE_PATH: &str = "profile.json";
+
+/// Traders not in the profile are assumed to be at this loyalty level
+pub const MAX_LOYALTY: u8 = 4;
+
+/// The hideout station whose level feeds the flea fee
+const INTEL_CENTER: &str = "Intelligence Center";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// quests handed in, by name
+    pub completed_quests: Vec<String>,
+    /// hideout station => level it's built to
+    pub hideout: HashMap<String, u8>,
+    /// trader => loyalty level, 0 for one that isn't unlocked yet
+    pub traders: HashMap<String, u8>,
+    /// the flea opens at level 15
+    pub flea_unlocked: bool,
+}
+
+impl Default for Profile {
+    /// Nothing done, but every trader maxed and the flea open, which is what prices assumed
+    /// before there were profiles
+    fn default() -> Self {
+        Self {
+            completed_quests: vec![],
+            hideout: HashMap::new(),
+            traders: HashMap::new(),
+            flea_unlocked: true,
+        }
+    }
+}
+
+impl Profile {
+    /// Load `profile.json`. A missing file is the default profile.
+    pub fn load() -> Result<Self, String> {
+        match fs::read_to_string(PROFILE_PATH) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{PROFILE_PATH}: {e}")),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{PROFILE_PATH}: {e}")),
+        }
+    }
+
+    pub fn loyalty(&self, trader: &str) -> u8 {
+        self.traders
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(trader))
+            .map_or(MAX_LOYALTY, |(_, level)| *level)
+    }
+
+    /// Whether we can sell to `trader` at all
+    pub fn can_sell_to(&self, trader: &str) -> bool {
+        self.loyalty(trader) > 0
+    }
+
+    fn station_level(&self, station: &str) -> Option<u8> {
+        self.hideout
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(station))
+            .map(|(_, level)| *level)
+    }
+
+    /// `fees` with the intel center level from the hideout, when the profile has one
+    pub fn fee_settings(&self, fees: FeeSettings) -> FeeSettings {
+        match self.station_level(INTEL_CENTER) {
+            Some(level) => FeeSettings {
+                intel_center_level: level,
+                ..fees
+            },
+            None => fees,
+        }
+    }
+
+    /// Whether `requirement` is already taken care of, so the item isn't needed for it anymore
+    pub fn done(&self, requirement: &Requirement) -> bool {
+        match &requirement.needed_for {
+            Need::Quest { name, .. } => self
+                .completed_quests
+                .iter()
+                .any(|quest| quest.eq_ignore_ascii_case(name)),
+            Need::Hideout { station, level } => {
+                self.station_level(station).is_some_and(|at| at >= *level)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traders_default_to_max() {
+        let profile: Profile =
+            serde_json::from_str(r#"{"traders": {"Jaeger": 0, "peacekeeper": 2}}"#).unwrap();
+        assert_eq!(profile.loyalty("Prapor"), MAX_LOYALTY);
+        assert_eq!(profile.loyalty("Peacekeeper"), 2);
+        assert!(!profile.can_sell_to("Jaeger"));
+        assert!(profile.flea_unlocked, "missing means unlocked");
+    }
+
+    #[test]
+    fn intel_center_feeds_fees() {
+        let fees = FeeSettings {
+            intel_center_level: 1,
+            hideout_management_level: 20,
+            ..Default::default()
+        };
+        assert_eq!(Profile::default().fee_settings(fees), fees);
+
+        let profile: Profile =
+            serde_json::from_str(r#"{"hideout": {"Intelligence Center": 3}}"#).unwrap();
+        let fees = profile.fee_settings(fees);
+        assert_eq!(fees.intel_center_level, 3);
+        assert_eq!(fees.hideout_management_level, 20);
+    }
+}
@@ -0,0 +1,248 @@
+//! Trader barters and hideout crafts, so we can tell when an item is worth more as an input
+//! than it sells for.
+//!
+//! The market api doesn't know about either, so they come from `recipes.json`. An item's
+//! value as an input is what the outputs sell for, less what the other inputs cost to buy,
+//! split over how many of the item the recipe takes. Parts are matched by bsg id when they
+//! have one, so a recipe written with English names still works in another client language.
+use std::{fs, io::ErrorKind, path::Path};
+
+use serde::Deserialize;
+
+use crate::{report::ItemReport, sell};
+
+pub const RECIPES_PATH: &str = "recipes.json";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Barter {
+        trader: String,
+        level: u8,
+    },
+    Craft {
+        station: String,
+        level: u8,
+        hours: f64,
+    },
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Barter { trader, level } => write!(f, "{trader} LL{level}"),
+            Kind::Craft {
+                station,
+                level,
+                hours,
+            } => write!(f, "{station} {level} ({hours}h)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Part {
+    pub item: String,
+    /// matched before the name when both sides have one
+    #[serde(default)]
+    pub bsg_id: String,
+    #[serde(default = "one")]
+    pub count: i64,
+}
+
+impl Part {
+    pub fn matches(&self, name: &str, bsg_id: &str) -> bool {
+        if !self.bsg_id.is_empty() && !bsg_id.is_empty() {
+            return self.bsg_id == bsg_id;
+        }
+        self.item.eq_ignore_ascii_case(name)
+    }
+}
+
+fn one() -> i64 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recipe {
+    pub kind: Kind,
+    pub inputs: Vec<Part>,
+    pub outputs: Vec<Part>,
+}
+
+impl Recipe {
+    /// How many of `item` this takes, 0 when it isn't an input
+    pub fn takes(&self, item: &ItemReport) -> i64 {
+        self.inputs
+            .iter()
+            .filter(|part| part.matches(&item.name, &item.bsg_id))
+            .map(|part| part.count)
+            .sum()
+    }
+}
+
+/// What one `item` is worth when it goes into `recipe`
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputValue {
+    pub recipe: Recipe,
+    /// rubles per item, can be negative when the other inputs cost more than the outputs
+    pub rubles: i64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecipeBook {
+    pub recipes: Vec<Recipe>,
+}
+
+impl RecipeBook {
+    /// Load `path`. A missing file is an empty book, nobody has to keep one.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map(|recipes| Self { recipes })
+                .map_err(|e| format!("{}: {e}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {e}", path.display())),
+        }
+    }
+
+    /// Every recipe that takes `item`
+    pub fn uses<'a>(&'a self, item: &'a ItemReport) -> impl Iterator<Item = &'a Recipe> {
+        self.recipes.iter().filter(move |r| r.takes(item) > 0)
+    }
+
+    /// What `item` is worth in each recipe that takes it, best first. `prices` gets the
+    /// report for any other part of the recipe. Recipes with a part we can't price are
+    /// left out.
+    pub fn input_values(
+        &self,
+        item: &ItemReport,
+        prices: impl Fn(&Part) -> Option<ItemReport>,
+    ) -> Vec<InputValue> {
+        let mut values: Vec<InputValue> = self
+            .uses(item)
+            .filter_map(|recipe| {
+                let outputs = recipe
+                    .outputs
+                    .iter()
+                    .map(|part| Some(part.count * sale_value(&prices(part)?)?))
+                    .sum::<Option<i64>>()?;
+                let others = recipe
+                    .inputs
+                    .iter()
+                    .filter(|part| !part.matches(&item.name, &item.bsg_id))
+                    .map(|part| Some(part.count * buy_cost(&prices(part)?)?))
+                    .sum::<Option<i64>>()?;
+
+                Some(InputValue {
+                    recipe: recipe.clone(),
+                    rubles: (outputs - others) / recipe.takes(item),
+                })
+            })
+            .collect();
+
+        values.sort_by_key(|value| std::cmp::Reverse(value.rubles));
+        values
+    }
+}
+
+/// What an output brings in, sold wherever pays best
+pub fn sale_value(report: &ItemReport) -> Option<i64> {
+    sell::recommend(report, None).map(|verdict| verdict.rubles)
+}
+
+/// What another input costs, bought at the lowest flea price. Items we can't buy there
+/// (banned, no market) have no cost we could know.
+pub fn buy_cost(report: &ItemReport) -> Option<i64> {
+    report.flea.first().map(|offer| offer.price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::market::sample_items;
+
+    fn book() -> RecipeBook {
+        RecipeBook {
+            recipes: serde_json::from_str(include_str!("../tests/fixtures/recipes.json")).unwrap(),
+        }
+    }
+
+    fn prices(part: &Part) -> Option<ItemReport> {
+        sample_items()
+            .iter()
+            .find(|item| part.matches(&item.name, &item.bsg_id))
+            .map(ItemReport::new)
+    }
+
+    fn report(name: &str) -> ItemReport {
+        prices(&Part {
+            item: name.to_owned(),
+            bsg_id: String::new(),
+            count: 1,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_and_find_uses() {
+        let book = book();
+        assert_eq!(
+            book.recipes[0].kind.to_string(),
+            "Therapist LL3",
+            "barters show trader and loyalty level"
+        );
+        assert_eq!(book.recipes[2].kind.to_string(), "Medstation 3 (1.5h)");
+        assert_eq!(book.uses(&report("Golden rooster figurine")).count(), 2);
+        assert_eq!(book.uses(&report("Dogtag case")).count(), 0);
+        assert_eq!(book.recipes[1].takes(&report("Bottle of water (0.6L)")), 4);
+    }
+
+    #[test]
+    fn rooster_is_worth_more_in_a_barter() {
+        let rooster = report("Golden rooster figurine");
+        let values = book().input_values(&rooster, prices);
+        assert_eq!(values.len(), 2);
+
+        // the dogtag case sold at its best, less the water we have to buy
+        let dogtags = sale_value(&report("Dogtag case")).unwrap();
+        let water = buy_cost(&report("Bottle of water (0.6L)")).unwrap();
+        assert_eq!(values[0].rubles, (dogtags - 4 * water) / 2);
+        assert!(values[0].rubles > sale_value(&rooster).unwrap());
+        assert!(values[0].rubles > values[1].rubles);
+    }
+
+    #[test]
+    fn unpriced_parts_skip_the_recipe() {
+        // the helmet can't be bought on the flea, so the craft that needs it can't be valued
+        let ledx = report("LEDX Skin Transilluminator");
+        assert!(book().input_values(&ledx, prices).is_empty());
+        assert_eq!(book().uses(&ledx).count(), 1);
+    }
+
+    #[test]
+    fn bsg_id_beats_name() {
+        // a russian client: the report's name is translated, the recipe's isn't
+        let mut water = report("Bottle of water (0.6L)");
+        water.name = "Бутылка воды (0,6л)".into();
+        let book = book();
+        assert_eq!(
+            book.recipes[0].takes(&water),
+            4,
+            "matched by the recipe's bsg id"
+        );
+        assert_eq!(
+            book.recipes[1].takes(&water),
+            0,
+            "no bsg id, and the name differs"
+        );
+    }
+
+    #[test]
+    fn missing_file_is_empty() {
+        let book = RecipeBook::load("no such recipes.json").unwrap();
+        assert!(book.recipes.is_empty());
+    }
+}
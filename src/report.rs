@@ -0,0 +1,332 @@
+//! Everything we show for one item, worked out once so the console printer and the overlay
+//! agree on the numbers.
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    apis::market::TarkovMarketItem,
+    config,
+    currency::Currency,
+    details::{self, Details},
+    fees::{best_listing, flea_fee, Listing, Suggestion},
+    lang::Strings,
+    profile,
+    requirements::{self, Requirement},
+    ruble_value, sell,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraderOffer {
+    pub name: String,
+    pub price: i64,
+    pub currency: Currency,
+}
+
+/// Which market price a flea row is based on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FleaBasis {
+    Lowest,
+    Avg24h,
+    Avg7d,
+}
+
+impl FleaBasis {
+    pub fn label(&self, text: &'static Strings) -> &'static str {
+        match self {
+            FleaBasis::Lowest => text.lowest,
+            FleaBasis::Avg24h => text.avg_24h,
+            FleaBasis::Avg7d => text.avg_7d,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FleaOffer {
+    pub basis: FleaBasis,
+    pub price: i64,
+    pub tax: i64,
+}
+
+impl FleaOffer {
+    pub fn net(&self) -> i64 {
+        self.price - self.tax
+    }
+}
+
+/// Whether the flea numbers can be trusted at all
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FleaStatus {
+    #[default]
+    Open,
+    /// can't be listed, any prices the api has are from before the ban
+    Banned,
+    /// nobody has traded it recently, prices are stale guesses
+    NoData,
+}
+
+impl FleaStatus {
+    pub fn label(&self, text: &'static Strings) -> &'static str {
+        match self {
+            FleaStatus::Open => text.flea_open,
+            FleaStatus::Banned => text.flea_banned,
+            FleaStatus::NoData => text.flea_no_data,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemReport {
+    /// the market api's id, what price samples are keyed by
+    #[serde(default)]
+    pub uid: String,
+    #[serde(default)]
+    pub bsg_id: String,
+    pub name: String,
+    pub short_name: String,
+    pub slots: i64,
+    /// the best trader, as quoted by the api
+    pub trader: TraderOffer,
+    /// everyone else who'd buy it, best first
+    #[serde(default)]
+    pub other_traders: Vec<TraderOffer>,
+    /// when it isn't `Open`, `flea` is empty and there's no suggestion
+    #[serde(default)]
+    pub flea_status: FleaStatus,
+    /// when the api last saw these prices change
+    #[serde(default)]
+    pub updated: Option<DateTime<Utc>>,
+    /// lowest price change over the last day and week, in percent
+    #[serde(default)]
+    pub diff24h: f64,
+    #[serde(default)]
+    pub diff7days: f64,
+    pub flea: Vec<FleaOffer>,
+    /// where to list it on the flea for the best net, if there's a market for it
+    #[serde(default)]
+    pub suggested: Option<Suggestion>,
+    /// quests and hideout upgrades that still want this item
+    #[serde(default)]
+    pub needed: Vec<Requirement>,
+    /// ballistics and such, for the kinds of item we know more about
+    #[serde(default)]
+    pub details: Option<Details>,
+    /// name of the value tier of wherever it sells best
+    #[serde(default)]
+    pub tier: String,
+}
+
+impl ItemReport {
+    pub fn new(item: &TarkovMarketItem) -> Self {
+        let config = config();
+        let fees = &profile().fee_settings(config.fees);
+
+        // the api also gives the trader price in rubles, which beats guessing at a currency
+        // we've never heard of
+        let (trader_price, currency) = match item.trader_price_cur {
+            Some(currency) => (item.trader_price, currency),
+            None => (item.trader_price_rub, Currency::Rouble),
+        };
+
+        let flea_status = if item.banned_on_flea {
+            FleaStatus::Banned
+        } else if !item.have_market_data {
+            FleaStatus::NoData
+        } else {
+            FleaStatus::Open
+        };
+
+        let flea = [
+            (FleaBasis::Lowest, item.price),
+            (FleaBasis::Avg24h, item.avg24h_price),
+            (FleaBasis::Avg7d, item.avg7days_price),
+        ]
+        .into_iter()
+        .map(|(basis, price)| FleaOffer {
+            basis,
+            price,
+            tax: flea_fee(fees, &Listing::single(item.base_price, price)),
+        })
+        .filter(|_| flea_status == FleaStatus::Open)
+        .collect();
+
+        let suggested = best_listing(
+            fees,
+            item.base_price,
+            item.price,
+            item.avg24h_price,
+            item.avg7days_price,
+            config.undercut_percent,
+        )
+        .filter(|_| flea_status == FleaStatus::Open);
+
+        let mut report = Self {
+            uid: item.uid.clone(),
+            bsg_id: item.bsg_id.clone(),
+            name: item.name.clone(),
+            short_name: item.short_name.clone(),
+            slots: item.slots.max(1),
+            trader: TraderOffer {
+                name: item.trader_name.clone(),
+                price: trader_price,
+                currency,
+            },
+            other_traders: sell::other_traders(item),
+            flea,
+            suggested,
+            flea_status,
+            updated: item.updated,
+            diff24h: item.diff24h,
+            diff7days: item.diff7days,
+            needed: requirements::still_needed(&item.name, &item.bsg_id, profile()),
+            details: details::details_for(&item.name, &item.bsg_id),
+            tier: String::new(),
+        };
+        if let Some(verdict) = sell::recommend(&report, None) {
+            let value = config.tier_basis.value(verdict.rubles, report.slots);
+            report.tier = config.tiers.name(value);
+        }
+        report
+    }
+
+    pub fn trader_rubles(&self) -> i64 {
+        ruble_value(self.trader.price, self.trader.currency)
+    }
+
+    /// What one costs: the lowest flea price, or the trader's when there's no flea
+    pub fn unit_price(&self) -> i64 {
+        self.flea
+            .first()
+            .map_or_else(|| self.trader_rubles(), |offer| offer.price)
+    }
+
+    /// `value` split across the item's slots, only for items bigger than 1x1
+    pub fn per_slot(&self, value: i64) -> Option<i64> {
+        if self.slots > 1 {
+            Some(value / self.slots)
+        } else {
+            None
+        }
+    }
+}
+
+/// Roughly how long ago, in the biggest unit that fits: `45s`, `12m`, `3h`, `5d`, `2y`
+pub fn format_age(age: TimeDelta) -> String {
+    let secs = age.num_seconds().max(0);
+    match secs {
+        s if s < 60 => format!("{s}s"),
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 24 * 60 * 60 => format!("{}h", s / (60 * 60)),
+        s if s < 365 * 24 * 60 * 60 => format!("{}d", s / (24 * 60 * 60)),
+        s => format!("{}y", s / (365 * 24 * 60 * 60)),
+    }
+}
+
+/// Whether prices came straight from the api or from the short lived response cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PriceSource {
+    Live,
+    Cached,
+}
+
+/// One hotkey press worth of results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lookup {
+    pub at: DateTime<Utc>,
+    /// what the ocr read off the screen
+    pub ocr_text: String,
+    /// the wiki title it was matched to, and what we searched the market for
+    pub title: String,
+    /// how well the ocr text matched `title`, 0.5 is perfect
+    pub score: f32,
+    pub source: PriceSource,
+    pub reports: Vec<ItemReport>,
+}
+
+impl Lookup {
+    pub fn new(
+        ocr_text: String,
+        title: String,
+        score: f32,
+        source: PriceSource,
+        items: &[TarkovMarketItem],
+    ) -> Self {
+        Self {
+            at: Utc::now(),
+            ocr_text,
+            title,
+            score,
+            source,
+            reports: items.iter().map(ItemReport::new).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::market::sample_items;
+
+    #[test]
+    fn open_items_get_flea_rows() {
+        let water = ItemReport::new(&sample_items()[0]);
+        assert_eq!(water.flea_status, FleaStatus::Open);
+        assert_eq!(water.flea.len(), 3);
+        assert!(water.suggested.is_some());
+    }
+
+    #[test]
+    fn banned_items_get_no_flea_rows() {
+        // the EXFIL sample is banned and has no market data, banned wins
+        let helmet = ItemReport::new(&sample_items()[4]);
+        assert_eq!(helmet.flea_status, FleaStatus::Banned);
+        assert!(helmet.flea.is_empty());
+        assert_eq!(helmet.suggested, None);
+        assert_eq!(
+            helmet.updated.map(|t| t.to_rfc3339()),
+            Some("2021-12-10T09:36:23.647+00:00".to_owned())
+        );
+    }
+
+    #[test]
+    fn stale_items_get_no_flea_rows() {
+        let mut water = sample_items()[0].clone();
+        water.have_market_data = false;
+        let report = ItemReport::new(&water);
+        assert_eq!(report.flea_status, FleaStatus::NoData);
+        assert!(report.flea.is_empty());
+        // still sellable to traders
+        assert_eq!(report.trader.price, 1544);
+    }
+
+    #[test]
+    fn unknown_currency_falls_back_to_rubles() {
+        let mut water = sample_items()[0].clone();
+        water.trader_price_cur = None;
+        water.trader_price = 10;
+        let report = ItemReport::new(&water);
+        assert_eq!(report.trader.currency, Currency::Rouble);
+        assert_eq!(report.trader.price, 1544);
+    }
+
+    #[test]
+    fn tier_names() {
+        let reports: Vec<String> = sample_items()
+            .iter()
+            .map(|item| ItemReport::new(item).tier)
+            .collect();
+        assert_eq!(
+            reports,
+            ["fair", "very rare", "great", "legendary", "junk"],
+            "by wherever each sells best"
+        );
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(format_age(TimeDelta::seconds(-5)), "0s");
+        assert_eq!(format_age(TimeDelta::seconds(59)), "59s");
+        assert_eq!(format_age(TimeDelta::minutes(90)), "1h");
+        assert_eq!(format_age(TimeDelta::days(3)), "3d");
+        assert_eq!(format_age(TimeDelta::days(800)), "2y");
+    }
+}
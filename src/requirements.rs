@@ -0,0 +1,174 @@
+//! Items that quests and hideout upgrades ask for, so a lookup can warn before one gets sold.
+//!
+//! The list lives in `requirements.json`, either written by hand or fetched from the
+//! tarkov.dev api when there isn't one yet, or on `refresh`. `profile.json` says which are
+//! already done.
+use std::{
+    fmt, fs,
+    io::{self, ErrorKind},
+    path::Path,
+    sync::RwLock,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::profile::Profile;
+
+pub const REQUIREMENTS_PATH: &str = "requirements.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Need {
+    Quest { name: String, trader: String },
+    Hideout { station: String, level: u8 },
+}
+
+impl fmt::Display for Need {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Need::Quest { name, trader } if trader.is_empty() => write!(f, "{name}"),
+            Need::Quest { name, trader } => write!(f, "{name} ({trader})"),
+            Need::Hideout { station, level } => write!(f, "{station} {level}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Requirement {
+    pub item: String,
+    /// matched before the name when both sides have one
+    #[serde(default)]
+    pub bsg_id: String,
+    pub count: i64,
+    #[serde(default)]
+    pub found_in_raid: bool,
+    pub needed_for: Need,
+}
+
+impl Requirement {
+    pub fn matches(&self, name: &str, bsg_id: &str) -> bool {
+        if !self.bsg_id.is_empty() && !bsg_id.is_empty() {
+            return self.bsg_id == bsg_id;
+        }
+        self.item.eq_ignore_ascii_case(name)
+    }
+
+    /// `2x found in raid`, `1x`
+    pub fn amount(&self) -> String {
+        if self.found_in_raid {
+            format!("{}x found in raid", self.count)
+        } else {
+            format!("{}x", self.count)
+        }
+    }
+}
+
+/// Requirements written by hand or kept from the last fetch. `None` means there's no file yet,
+/// so they should be fetched. A file that doesn't parse is an error, not a reason to replace it.
+pub fn load(path: impl AsRef<Path>) -> Result<Option<Vec<Requirement>>, String> {
+    let path = path.as_ref();
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("{}: {e}", path.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{}: {e}", path.display())),
+    }
+}
+
+/// Pretty printed, the file is meant to be edited
+pub fn save(path: impl AsRef<Path>, requirements: &[Requirement]) -> io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(requirements)?)
+}
+
+/// Empty until `main` loads the file or a fetch comes back
+static REQUIREMENTS: RwLock<Vec<Requirement>> = RwLock::new(Vec::new());
+
+pub fn set_requirements(requirements: Vec<Requirement>) {
+    *REQUIREMENTS.write().unwrap() = requirements;
+}
+
+/// What the item is still needed for, quests first
+pub fn still_needed(name: &str, bsg_id: &str, profile: &Profile) -> Vec<Requirement> {
+    filter(&REQUIREMENTS.read().unwrap(), name, bsg_id, profile)
+}
+
+fn filter(all: &[Requirement], name: &str, bsg_id: &str, profile: &Profile) -> Vec<Requirement> {
+    let mut needed: Vec<Requirement> = all
+        .iter()
+        .filter(|r| r.matches(name, bsg_id) && !profile.done(r))
+        .cloned()
+        .collect();
+    needed.sort_by_key(|r| matches!(r.needed_for, Need::Hideout { .. }));
+    needed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    fn requirements() -> Vec<Requirement> {
+        serde_json::from_str(
+            r#"[
+                {"item": "Golden rooster figurine", "count": 1, "needed_for": {"hideout": {"station": "Lavatory", "level": 3}}},
+                {"item": "LEDX Skin Transilluminator", "bsg_id": "5c0530ee86f774697952d952", "count": 1,
+                 "found_in_raid": true, "needed_for": {"quest": {"name": "Private Clinic", "trader": "Therapist"}}},
+                {"item": "LEDX Skin Transilluminator", "count": 1, "needed_for": {"hideout": {"station": "Medstation", "level": 3}}}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn quests_first_and_described() {
+        let needed = filter(
+            &requirements(),
+            "LEDX Skin Transilluminator",
+            "",
+            &Profile::default(),
+        );
+        assert_eq!(needed.len(), 2);
+        assert_eq!(needed[0].amount(), "1x found in raid");
+        assert_eq!(
+            needed[0].needed_for.to_string(),
+            "Private Clinic (Therapist)"
+        );
+        assert_eq!(needed[1].needed_for.to_string(), "Medstation 3");
+    }
+
+    #[test]
+    fn bsg_id_beats_name() {
+        let all = requirements();
+        assert!(all[1].matches("LEDX", "5c0530ee86f774697952d952"));
+        assert!(!all[1].matches("LEDX Skin Transilluminator", "someotherid"));
+        // no id on the requirement, fall back to the name
+        assert!(all[0].matches("golden rooster figurine", "5bc9bc53d4351e00367fbcee"));
+    }
+
+    #[test]
+    fn profile_marks_done() {
+        let profile: Profile = serde_json::from_str(
+            r#"{"completed_quests": ["private clinic"], "hideout": {"Medstation": 2, "Lavatory": 3}}"#,
+        )
+        .unwrap();
+        let all = requirements();
+        assert!(filter(&all, "Golden rooster figurine", "", &profile).is_empty());
+
+        let needed = filter(&all, "LEDX Skin Transilluminator", "", &profile);
+        assert_eq!(needed, [all[2].clone()], "medstation is only at 2");
+    }
+
+    #[test]
+    fn load_tells_missing_from_broken() {
+        let path = TempPath::new("requirements.json");
+        assert_eq!(load(&path), Ok(None));
+
+        save(&path, &requirements()).unwrap();
+        assert_eq!(load(&path), Ok(Some(requirements())));
+
+        // a hand edit with a trailing comma
+        fs::write(&path, r#"[{"item": "LEDX", "count": 1,}]"#).unwrap();
+        assert!(load(&path).unwrap_err().contains("requirements.json"));
+    }
+}
@@ -0,0 +1,293 @@
+//! Where to sell an item: every trader that would buy it, or the flea.
+//!
+//! The market api only quotes the best trader. What the others pay comes from the tarkov.dev
+//! api, and is kept in `trader_prices.json` until it's a day old.
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+    sync::RwLock,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    apis::market::TarkovMarketItem,
+    lang::Strings,
+    prices::WindowStats,
+    profile,
+    profile::Profile,
+    report::{FleaStatus, ItemReport, TraderOffer},
+    ruble_value,
+};
+
+pub const TRADER_PRICES_PATH: &str = "trader_prices.json";
+
+/// Every trader's offer for one item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraderPrices {
+    pub bsg_id: String,
+    pub name: String,
+    pub offers: Vec<TraderOffer>,
+}
+
+/// Trader prices follow patches and the exchange rates, a day old is old enough to fetch again
+pub const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What traders paid at the last fetch, `None` before the first one. A file that doesn't parse
+/// is an error, so it doesn't get fetched over before anyone sees why.
+pub fn load(path: impl AsRef<Path>) -> Result<Option<Vec<TraderPrices>>, String> {
+    let path = path.as_ref();
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("{}: {e}", path.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{}: {e}", path.display())),
+    }
+}
+
+/// How long ago the prices were saved, `None` if they never were
+pub fn age(path: impl AsRef<Path>) -> Option<Duration> {
+    fs::metadata(path).ok()?.modified().ok()?.elapsed().ok()
+}
+
+/// One entry per item that any trader buys
+pub fn save(path: impl AsRef<Path>, prices: &[TraderPrices]) -> io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(prices)?)
+}
+
+/// Empty until `main` loads the file or a fetch comes back
+static TRADER_PRICES: RwLock<Vec<TraderPrices>> = RwLock::new(Vec::new());
+
+pub fn set_trader_prices(prices: Vec<TraderPrices>) {
+    *TRADER_PRICES.write().unwrap() = prices;
+}
+
+/// The English name tarkov.dev has for `bsg_id`, if any trader buys it
+pub fn name_for(bsg_id: &str) -> Option<String> {
+    TRADER_PRICES
+        .read()
+        .unwrap()
+        .iter()
+        .find(|p| p.bsg_id == bsg_id)
+        .map(|p| p.name.clone())
+}
+
+/// Offers from every trader other than the one the api quoted, best first
+pub fn other_traders(item: &TarkovMarketItem) -> Vec<TraderOffer> {
+    others(&TRADER_PRICES.read().unwrap(), item)
+}
+
+fn others(all: &[TraderPrices], item: &TarkovMarketItem) -> Vec<TraderOffer> {
+    let by_id = all
+        .iter()
+        .find(|p| !item.bsg_id.is_empty() && p.bsg_id == item.bsg_id);
+    let Some(prices) =
+        by_id.or_else(|| all.iter().find(|p| p.name.eq_ignore_ascii_case(&item.name)))
+    else {
+        return vec![];
+    };
+
+    let mut offers: Vec<TraderOffer> = prices
+        .offers
+        .iter()
+        .filter(|offer| offer.name != item.trader_name && offer.price > 0)
+        .cloned()
+        .collect();
+    offers.sort_by_key(|offer| std::cmp::Reverse(ruble_value(offer.price, offer.currency)));
+    offers
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Venue {
+    Trader(String),
+    Flea,
+}
+
+impl Venue {
+    /// The trader's name, or what the flea is called in `text`'s language
+    pub fn label<'a>(&'a self, text: &'a Strings) -> &'a str {
+        match self {
+            Venue::Trader(name) => name,
+            Venue::Flea => text.flea,
+        }
+    }
+}
+
+/// A flea price this far (in percent) under the recent median is worth waiting out
+const HOLD_PERCENT: f64 = -10.0;
+
+/// The best place to sell, in rubles after any fee, and how far ahead of second place it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub venue: Venue,
+    pub rubles: i64,
+    pub runner_up: Option<(Venue, i64)>,
+    /// set when the flea wins but its lowest price is unusually low right now, by how much
+    /// it's under the median in percent
+    pub under_median: Option<f64>,
+}
+
+impl Verdict {
+    pub fn margin(&self) -> Option<i64> {
+        self.runner_up
+            .as_ref()
+            .map(|(_, rubles)| self.rubles - rubles)
+    }
+}
+
+/// Compare every trader against the flea. The flea counts at the suggested listing price
+/// if there is one, and not at all for items that are banned there or have no market data.
+/// `recent` is the item's price stats over the last while, if we have any.
+///
+/// Only traders and the flea the player's profile has unlocked are considered.
+pub fn recommend(report: &ItemReport, recent: Option<&WindowStats>) -> Option<Verdict> {
+    recommend_for(profile(), report, recent)
+}
+
+fn recommend_for(
+    profile: &Profile,
+    report: &ItemReport,
+    recent: Option<&WindowStats>,
+) -> Option<Verdict> {
+    let mut options: Vec<(Venue, i64)> = std::iter::once(&report.trader)
+        .chain(&report.other_traders)
+        .filter(|offer| offer.price > 0 && profile.can_sell_to(&offer.name))
+        .map(|offer| {
+            let rubles = ruble_value(offer.price, offer.currency);
+            (Venue::Trader(offer.name.clone()), rubles)
+        })
+        .collect();
+
+    if report.flea_status == FleaStatus::Open && profile.flea_unlocked {
+        let flea = match &report.suggested {
+            Some(suggested) => Some(suggested.net()),
+            None => report.flea.first().map(|offer| offer.net()),
+        };
+        if let Some(net) = flea.filter(|&net| net > 0) {
+            options.push((Venue::Flea, net));
+        }
+    }
+
+    // stable, so a tie goes to the trader: no waiting for a buyer
+    options.sort_by_key(|(_, rubles)| std::cmp::Reverse(*rubles));
+    let mut options = options.into_iter();
+    let (venue, rubles) = options.next()?;
+
+    let under_median = match (&venue, recent, report.flea.first()) {
+        (Venue::Flea, Some(stats), Some(lowest)) if stats.count > 1 => {
+            Some(stats.vs_median(lowest.price)).filter(|&p| p <= HOLD_PERCENT)
+        }
+        _ => None,
+    };
+
+    Some(Verdict {
+        venue,
+        rubles,
+        runner_up: options.next(),
+        under_median,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apis::market::sample_items, currency::Currency, test_util::TempPath};
+
+    fn trader_prices() -> Vec<TraderPrices> {
+        serde_json::from_str(include_str!("../tests/fixtures/trader_prices.json")).unwrap()
+    }
+
+    #[test]
+    fn load_and_age() {
+        let path = TempPath::new("trader_prices.json");
+        assert_eq!(load(&path), Ok(None));
+        assert_eq!(age(&path), None);
+
+        save(&path, &trader_prices()).unwrap();
+        assert_eq!(load(&path), Ok(Some(trader_prices())));
+        assert!(age(&path).is_some_and(|age| age < MAX_AGE));
+
+        fs::write(&path, "not json").unwrap();
+        assert!(load(&path).unwrap_err().contains("trader_prices.json"));
+    }
+
+    fn report(index: usize) -> ItemReport {
+        let item = &sample_items()[index];
+        let mut report = ItemReport::new(item);
+        report.other_traders = others(&trader_prices(), item);
+        report
+    }
+
+    #[test]
+    fn other_traders_best_first() {
+        let prices = trader_prices();
+        let water = &sample_items()[0];
+        let offers = others(&prices, water);
+        let names: Vec<&str> = offers.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, ["Jaeger", "Fence"], "the quoted trader is left out");
+        assert_eq!(offers[0].price, 1470);
+
+        // dollars sort by what they're worth in rubles
+        let offers = others(&prices, &sample_items()[4]);
+        assert_eq!(offers[0].name, "Peacekeeper");
+        assert_eq!(offers[0].currency, Currency::Dollar);
+
+        // by name when the bsg id isn't known
+        let mut water = water.clone();
+        water.bsg_id.clear();
+        assert_eq!(others(&prices, &water).len(), 2);
+        assert!(others(&[], &water).is_empty());
+    }
+
+    #[test]
+    fn flea_wins_for_water() {
+        let verdict = recommend(&report(0), None).unwrap();
+        assert_eq!(verdict.venue, Venue::Flea);
+        assert_eq!(
+            verdict.runner_up,
+            Some((Venue::Trader("Therapist".into()), 1544))
+        );
+        assert_eq!(verdict.margin(), Some(verdict.rubles - 1544));
+    }
+
+    #[test]
+    fn banned_items_go_to_traders() {
+        let helmet = report(4);
+        assert_eq!(helmet.flea_status, FleaStatus::Banned);
+        let verdict = recommend(&helmet, None).unwrap();
+        assert_eq!(verdict.venue, Venue::Trader("Ragman".into()));
+        assert_eq!(verdict.rubles, 3142);
+        assert!(matches!(verdict.runner_up, Some((Venue::Trader(_), _))));
+    }
+
+    #[test]
+    fn cheap_flea_suggests_holding() {
+        // water is at 12,777 now
+        let recent = |median| WindowStats {
+            count: 10,
+            min: 11000,
+            max: 16000,
+            median,
+        };
+        let verdict = recommend(&report(0), Some(&recent(15000))).unwrap();
+        assert_eq!(verdict.venue, Venue::Flea);
+        assert!((verdict.under_median.unwrap() - -14.82).abs() < 0.01);
+
+        let verdict = recommend(&report(0), Some(&recent(13000))).unwrap();
+        assert_eq!(verdict.under_median, None);
+    }
+
+    #[test]
+    fn only_unlocked_venues() {
+        let profile: Profile =
+            serde_json::from_str(r#"{"traders": {"Therapist": 0}, "flea_unlocked": false}"#)
+                .unwrap();
+        let verdict = recommend_for(&profile, &report(0), None).unwrap();
+        assert_eq!(verdict.venue, Venue::Trader("Jaeger".into()));
+        assert_eq!(verdict.rubles, 1470);
+        assert_eq!(verdict.runner_up.unwrap().0, Venue::Trader("Fence".into()));
+    }
+}
@@ -0,0 +1,149 @@
+//! Pricing a whole stash page at once. OCR reads the short names printed on the item icons, and
+//! each one is valued from the `top` snapshot, so a scan doesn't spend any api quota.
+use std::collections::HashMap;
+
+use crate::{
+    apis::market::{Root, TarkovMarketItem},
+    ocr_eval::edit_distance,
+    report::ItemReport,
+    sell::{self, Venue},
+};
+
+/// Short names can have a few words, like `Gas an.`
+const MAX_WORDS: usize = 3;
+
+/// Shorter labels are too easy to confuse with a misread to allow any typo
+const FUZZY_MIN_LEN: usize = 4;
+
+/// One kind of item found in the stash
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub name: String,
+    pub count: usize,
+    /// where it sells best, and for how much each
+    pub venue: Venue,
+    pub rubles: i64,
+}
+
+impl Row {
+    pub fn total(&self) -> i64 {
+        self.rubles * self.count as i64
+    }
+}
+
+/// Every item whose short name appears in the OCR `lines`, most valuable in total first.
+/// Icons next to each other often come back as one line, so runs of words are tried too.
+pub fn scan(lines: &[String], items: &Root) -> Vec<Row> {
+    let mut by_label: HashMap<String, &TarkovMarketItem> = HashMap::new();
+    for item in items {
+        by_label
+            .entry(item.short_name.to_lowercase())
+            .or_insert(item);
+    }
+
+    let mut counts: Vec<(&TarkovMarketItem, usize)> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let mut start = 0;
+        while start < words.len() {
+            let longest = MAX_WORDS.min(words.len() - start);
+            let found = (1..=longest).rev().find_map(|len| {
+                let label = words[start..start + len].join(" ");
+                identify(&label, &by_label, items).map(|item| (item, len))
+            });
+            match found {
+                Some((item, len)) => {
+                    match counts.iter_mut().find(|(known, _)| known.uid == item.uid) {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((item, 1)),
+                    }
+                    start += len;
+                }
+                None => start += 1,
+            }
+        }
+    }
+
+    let mut rows: Vec<Row> = counts
+        .into_iter()
+        .filter_map(|(item, count)| {
+            let report = ItemReport::new(item);
+            let verdict = sell::recommend(&report, None)?;
+            Some(Row {
+                name: report.name,
+                count,
+                venue: verdict.venue,
+                rubles: verdict.rubles,
+            })
+        })
+        .collect();
+    rows.sort_by_key(|row| std::cmp::Reverse(row.total()));
+    rows
+}
+
+/// An exact short name, or one typo away from one. Ties go to the first item in the list, so
+/// the same scan always finds the same items.
+fn identify<'a>(
+    label: &str,
+    by_label: &HashMap<String, &'a TarkovMarketItem>,
+    items: &'a Root,
+) -> Option<&'a TarkovMarketItem> {
+    let label = label.to_lowercase();
+    if let Some(item) = by_label.get(&label) {
+        return Some(item);
+    }
+    if label.chars().count() < FUZZY_MIN_LEN {
+        return None;
+    }
+    items
+        .iter()
+        .filter(|item| item.short_name.chars().count() >= FUZZY_MIN_LEN)
+        .map(|item| (item, edit_distance(&label, &item.short_name.to_lowercase())))
+        .filter(|(_, distance)| *distance <= 1)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(item, _)| item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::market::sample_items;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn counts_labels_across_lines() {
+        let rows = scan(&lines(&["LEDX Water", "water", "Rooster"]), &sample_items());
+        let found: Vec<(&str, usize)> = rows.iter().map(|r| (r.name.as_str(), r.count)).collect();
+        assert_eq!(
+            found,
+            [
+                ("LEDX Skin Transilluminator", 1),
+                ("Golden rooster figurine", 1),
+                ("Bottle of water (0.6L)", 2),
+            ]
+        );
+        assert!(rows.windows(2).all(|w| w[0].total() >= w[1].total()));
+    }
+
+    #[test]
+    fn tolerates_one_misread() {
+        let rows = scan(&lines(&["Dogtaqs", "EXF1L"]), &sample_items());
+        let names: Vec<&str> = rows.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "Dogtag case",
+                "Team Wendy EXFIL Ballistic Helmet (Coyote Brown)"
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_other_text() {
+        let rows = scan(&lines(&["STASH", "Sort", "Lx", "3/5"]), &sample_items());
+        assert_eq!(rows, []);
+    }
+}
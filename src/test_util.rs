@@ -0,0 +1,43 @@
+//! Helpers shared by the unit tests.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A path in the temp dir for one test, removed again when it goes out of scope, even when the
+/// test panics. Nothing is created up front, so it can become a file or a directory.
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    /// `name` only has to be unique among the tests, the process id keeps separate runs apart
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("tpo_{}_{}", std::process::id(), name));
+        let temp = Self(path);
+        temp.remove();
+        temp
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    fn remove(&self) {
+        if self.0.is_dir() {
+            let _ = fs::remove_dir_all(&self.0);
+        } else {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
@@ -0,0 +1,284 @@
+//! Value tiers: which color (and name) a ruble value gets, in the console, the dashboard,
+//! the overlay and the history log alike.
+//!
+//! Tiers come from the config, either one of the presets or a list of thresholds, checked
+//! lowest first. The first tier whose `up_to` is at least the value wins, and anything past
+//! the last threshold gets the last tier.
+use colored::Color;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+/// A color name `colored` knows (`"bright red"`, `"bright_red"`) or `"#rrggbb"`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TierColor(pub Color);
+
+impl TryFrom<String> for TierColor {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        if let Some(hex) = text.strip_prefix('#') {
+            let channel = |i: usize| {
+                hex.get(i..i + 2)
+                    .and_then(|c| u8::from_str_radix(c, 16).ok())
+            };
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(TierColor(Color::TrueColor { r, g, b })),
+                _ => Err(format!("bad hex color {text:?}")),
+            };
+        }
+        text.replace('_', " ")
+            .parse()
+            .map(TierColor)
+            .map_err(|()| format!("unknown color {text:?}"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tier {
+    /// highest ruble value in this tier, `None` for everything above the previous one
+    #[serde(default)]
+    pub up_to: Option<i64>,
+    pub color: TierColor,
+    #[serde(default)]
+    pub background: Option<TierColor>,
+    /// what the tier is called in the history log and the color table
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+impl Tier {
+    fn new(up_to: Option<i64>, color: Color, background: Option<Color>, label: &str) -> Self {
+        Self {
+            up_to,
+            color: TierColor(color),
+            background: background.map(TierColor),
+            label: Some(label.to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    Default,
+    /// blues, yellows and oranges that stay apart with any kind of color blindness
+    Colorblind,
+}
+
+#[rustfmt::skip]
+static DEFAULT: Lazy<Vec<Tier>> = Lazy::new(|| vec![
+    Tier::new(Some(5000),   Color::White,     None,               "junk"),
+    Tier::new(Some(10000),  Color::Blue,      None,               "low"),
+    Tier::new(Some(25000),  Color::Cyan,      None,               "fair"),
+    Tier::new(Some(50000),  Color::Magenta,   None,               "good"),
+    Tier::new(Some(100000), Color::Green,     None,               "great"),
+    Tier::new(Some(200000), Color::Yellow,    None,               "rare"),
+    Tier::new(Some(300000), Color::Red,       None,               "very rare"),
+    Tier::new(Some(500000), Color::BrightRed, None,               "exceptional"),
+    Tier::new(None,         Color::Black,     Some(Color::White), "legendary"),
+]);
+
+/// Okabe-Ito colors, same thresholds as the default
+#[rustfmt::skip]
+static COLORBLIND: Lazy<Vec<Tier>> = Lazy::new(|| {
+    let rgb = |r, g, b| Color::TrueColor { r, g, b };
+    vec![
+        Tier::new(Some(5000),   rgb(187, 187, 187), None,                     "junk"),
+        Tier::new(Some(10000),  rgb(0, 114, 178),   None,                     "low"),
+        Tier::new(Some(25000),  rgb(86, 180, 233),  None,                     "fair"),
+        Tier::new(Some(50000),  rgb(0, 158, 115),   None,                     "good"),
+        Tier::new(Some(100000), rgb(240, 228, 66),  None,                     "great"),
+        Tier::new(Some(200000), rgb(230, 159, 0),   None,                     "rare"),
+        Tier::new(Some(300000), rgb(213, 94, 0),    None,                     "very rare"),
+        Tier::new(Some(500000), rgb(204, 121, 167), None,                     "exceptional"),
+        Tier::new(None,         Color::Black,       Some(rgb(240, 228, 66)), "legendary"),
+    ]
+});
+
+/// `"tiers": "colorblind"` or `"tiers": [{"up_to": 10000, "color": "blue"}, ...]`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "TierConfigRaw")]
+pub enum TierConfig {
+    Preset(Preset),
+    Custom(Vec<Tier>),
+}
+
+impl Default for TierConfig {
+    fn default() -> Self {
+        TierConfig::Preset(Preset::Default)
+    }
+}
+
+/// What the config says before the thresholds have been checked
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TierConfigRaw {
+    Preset(Preset),
+    Custom(Vec<Tier>),
+}
+
+impl TryFrom<TierConfigRaw> for TierConfig {
+    type Error = String;
+
+    fn try_from(raw: TierConfigRaw) -> Result<Self, Self::Error> {
+        let tiers = match raw {
+            TierConfigRaw::Preset(preset) => return Ok(TierConfig::Preset(preset)),
+            TierConfigRaw::Custom(tiers) => tiers,
+        };
+        let Some((_, rest)) = tiers.split_last() else {
+            return Err("tiers can't be empty".to_owned());
+        };
+        if rest.iter().any(|tier| tier.up_to.is_none()) {
+            return Err("only the last tier can leave out up_to".to_owned());
+        }
+        let bounds: Vec<i64> = tiers.iter().filter_map(|tier| tier.up_to).collect();
+        if let Some(pair) = bounds.windows(2).find(|pair| pair[1] <= pair[0]) {
+            return Err(format!("tier up_to {} isn't above {}", pair[1], pair[0]));
+        }
+        Ok(TierConfig::Custom(tiers))
+    }
+}
+
+impl TierConfig {
+    pub fn tiers(&self) -> &[Tier] {
+        match self {
+            TierConfig::Preset(Preset::Default) => &DEFAULT,
+            TierConfig::Preset(Preset::Colorblind) => &COLORBLIND,
+            TierConfig::Custom(tiers) => tiers,
+        }
+    }
+
+    /// Which tier `rubles` falls in, counting from 0
+    pub fn index(&self, rubles: i64) -> usize {
+        let tiers = self.tiers();
+        tiers
+            .iter()
+            .position(|tier| tier.up_to.is_none_or(|up_to| rubles <= up_to))
+            .unwrap_or(tiers.len() - 1)
+    }
+
+    pub fn tier(&self, rubles: i64) -> &Tier {
+        &self.tiers()[self.index(rubles)]
+    }
+
+    /// The tier's label, or `tier 3` when it doesn't have one
+    pub fn name(&self, rubles: i64) -> String {
+        let index = self.index(rubles);
+        match &self.tiers()[index].label {
+            Some(label) => label.clone(),
+            None => format!("tier {}", index + 1),
+        }
+    }
+}
+
+/// Whether a price is tiered as it is, or per slot the item takes up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TierBasis {
+    #[default]
+    Total,
+    PerSlot,
+}
+
+impl TierBasis {
+    /// What `rubles` for an item of `slots` counts as. Anything under 1 slot counts as 1.
+    pub fn value(self, rubles: i64, slots: i64) -> i64 {
+        match self {
+            TierBasis::Total => rubles,
+            TierBasis::PerSlot => rubles / slots.max(1),
+        }
+    }
+
+    /// Column header for the color table
+    pub fn describe(self) -> &'static str {
+        match self {
+            TierBasis::Total => "total",
+            TierBasis::PerSlot => "per slot",
+        }
+    }
+}
+
+/// Whether the user asked for no colors, see <https://no-color.org>. The console gets this
+/// from `colored` already, the dashboard has to check.
+pub fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<TierConfig, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn presets() {
+        let tiers = TierConfig::default();
+        assert_eq!(tiers.tier(2500).color.0, Color::White);
+        assert_eq!(tiers.tier(5001).color.0, Color::Blue);
+        assert_eq!(tiers.name(2_000_000), "legendary");
+        assert_eq!(
+            tiers.tier(2_000_000).background,
+            Some(TierColor(Color::White))
+        );
+
+        let colorblind = parse(r#""colorblind""#).unwrap();
+        assert_eq!(colorblind.tiers().len(), tiers.tiers().len());
+        assert_eq!(colorblind.name(60000), "great");
+    }
+
+    #[test]
+    fn custom_tiers() {
+        let tiers = parse(
+            r##"[
+                {"up_to": 20000, "color": "bright_black"},
+                {"up_to": 100000, "color": "#ff8800", "label": "keep"},
+                {"color": "black", "background": "bright yellow", "label": "jackpot"}
+            ]"##,
+        )
+        .unwrap();
+        assert_eq!(tiers.name(15000), "tier 1");
+        assert_eq!(tiers.tier(15000).color.0, Color::BrightBlack);
+        assert_eq!(
+            tiers.tier(20001).color.0,
+            Color::TrueColor {
+                r: 255,
+                g: 136,
+                b: 0
+            }
+        );
+        assert_eq!(tiers.name(1_000_000), "jackpot");
+
+        // everything past the last threshold lands in the last tier
+        let capped =
+            parse(r#"[{"up_to": 10, "color": "red"}, {"up_to": 20, "color": "blue"}]"#).unwrap();
+        assert_eq!(capped.index(500), 1);
+    }
+
+    #[test]
+    fn per_slot_basis() {
+        assert_eq!(TierBasis::Total.value(100000, 4), 100000);
+        assert_eq!(TierBasis::PerSlot.value(100000, 4), 25000);
+        // bad slot counts are treated as 1x1 rather than dividing by them
+        assert_eq!(TierBasis::PerSlot.value(100000, 0), 100000);
+        assert_eq!(TierBasis::PerSlot.value(100000, -2), 100000);
+
+        let basis: TierBasis = serde_json::from_str(r#""per_slot""#).unwrap();
+        assert_eq!(basis, TierBasis::PerSlot);
+    }
+
+    #[test]
+    fn bad_tiers() {
+        assert!(parse(r#"[]"#).is_err());
+        assert!(
+            parse(r#"[{"up_to": 10, "color": "red"}, {"up_to": 5, "color": "blue"}]"#).is_err()
+        );
+        assert!(parse(r#"[{"color": "red"}, {"up_to": 5, "color": "blue"}]"#).is_err());
+        assert!(parse(r#"[{"up_to": 10, "color": "mauve"}]"#).is_err());
+        assert!(parse(r##"[{"up_to": 10, "color": "#12345"}]"##).is_err());
+        assert!(parse(r#""rainbow""#).is_err());
+    }
+}
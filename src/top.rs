@@ -0,0 +1,115 @@
+//! Rubles per slot for every item in a local snapshot of the market, for deciding what's
+//! worth picking up in raid.
+//!
+//! The snapshot is the api's full item list saved to `items.json`, so ranking over and over
+//! with different tags doesn't spend the api quota.
+use std::{fs, io, path::Path};
+
+use crate::{
+    apis::market::{parse_items, Root},
+    report::ItemReport,
+    sell::{self, Venue},
+};
+
+pub const SNAPSHOT_PATH: &str = "items.json";
+
+/// `None` if there's no snapshot yet. Items that no longer parse are skipped.
+pub fn load(path: impl AsRef<Path>) -> Option<Root> {
+    let text = fs::read_to_string(path).ok()?;
+    parse_items(&text).ok().map(|(items, _)| items)
+}
+
+/// Compact rather than pretty, the full list is several megabytes as it is
+pub fn save(path: impl AsRef<Path>, items: &Root) -> io::Result<()> {
+    fs::write(path, serde_json::to_string(items)?)
+}
+
+/// One row of the loot table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ranked {
+    pub name: String,
+    pub slots: i64,
+    /// where it sells best, and for how much in total
+    pub venue: Venue,
+    pub rubles: i64,
+    pub per_slot: i64,
+}
+
+/// Every item with any of `tags` (all of them when `tags` is empty), best rubles per slot
+/// first. Each item is valued wherever it sells best for the player's profile.
+pub fn rank(items: &Root, tags: &[String]) -> Vec<Ranked> {
+    let mut ranked: Vec<Ranked> = items
+        .iter()
+        .filter(|item| {
+            tags.is_empty()
+                || item
+                    .tags
+                    .iter()
+                    .any(|tag| tags.iter().any(|want| want.eq_ignore_ascii_case(tag)))
+        })
+        .filter_map(|item| {
+            let report = ItemReport::new(item);
+            let verdict = sell::recommend(&report, None)?;
+            let slots = report.slots.max(1);
+            Some(Ranked {
+                name: report.name,
+                slots,
+                venue: verdict.venue,
+                rubles: verdict.rubles,
+                per_slot: verdict.rubles / slots,
+            })
+        })
+        .collect();
+
+    ranked.sort_by_key(|r| std::cmp::Reverse(r.per_slot));
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apis::market::sample_items, test_util::TempPath};
+
+    #[test]
+    fn ranks_by_rubles_per_slot() {
+        let ranked = rank(&sample_items(), &[]);
+        let names: Vec<&str> = ranked.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "LEDX Skin Transilluminator",
+                "Dogtag case",
+                "Golden rooster figurine",
+                "Bottle of water (0.6L)",
+                "Team Wendy EXFIL Ballistic Helmet (Coyote Brown)",
+            ]
+        );
+
+        // the banned helmet only sells to traders, split over its 4 slots
+        let helmet = &ranked[4];
+        assert_eq!(helmet.venue, Venue::Trader("Ragman".into()));
+        assert_eq!((helmet.rubles, helmet.per_slot), (3142, 785));
+    }
+
+    #[test]
+    fn filter_by_tags() {
+        let ranked = rank(&sample_items(), &["barter".into(), "Drinks".into()]);
+        let names: Vec<&str> = ranked.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "LEDX Skin Transilluminator",
+                "Golden rooster figurine",
+                "Bottle of water (0.6L)"
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let path = TempPath::new("items.json");
+        assert_eq!(load(&path), None);
+        save(&path, &sample_items()).unwrap();
+        assert_eq!(load(&path), Some(sample_items()));
+    }
+}
@@ -0,0 +1,81 @@
+//! Which way prices are heading: the api's 24h/7d change, and a sparkline of the latest price
+//! samples.
+
+/// Changes smaller than this (in percent) are just noise
+const FLAT_PERCENT: f64 = 0.5;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Flat,
+}
+
+impl Direction {
+    pub fn of(percent: f64) -> Self {
+        if percent >= FLAT_PERCENT {
+            Direction::Up
+        } else if percent <= -FLAT_PERCENT {
+            Direction::Down
+        } else {
+            Direction::Flat
+        }
+    }
+
+    pub fn arrow(&self) -> &'static str {
+        match self {
+            Direction::Up => "▲",
+            Direction::Down => "▼",
+            Direction::Flat => "=",
+        }
+    }
+}
+
+/// `▲ +2.4%`
+pub fn format_change(percent: f64) -> String {
+    format!("{} {:+.1}%", Direction::of(percent).arrow(), percent)
+}
+
+/// One bar per value, scaled between the smallest and largest. Empty for fewer than two
+/// values, there's no trend in a single point.
+pub fn sparkline(values: &[i64]) -> String {
+    if values.len() < 2 {
+        return String::new();
+    }
+    let min = *values.iter().min().unwrap();
+    let max = *values.iter().max().unwrap();
+
+    values
+        .iter()
+        .map(|&v| {
+            if max == min {
+                BARS[BARS.len() / 2]
+            } else {
+                let scaled = (v - min) as f64 / (max - min) as f64;
+                BARS[(scaled * (BARS.len() - 1) as f64).round() as usize]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes() {
+        assert_eq!(format_change(2.43), "▲ +2.4%");
+        assert_eq!(format_change(-5.07), "▼ -5.1%");
+        assert_eq!(format_change(0.3), "= +0.3%");
+    }
+
+    #[test]
+    fn sparklines() {
+        assert_eq!(sparkline(&[]), "");
+        assert_eq!(sparkline(&[12000]), "");
+        assert_eq!(sparkline(&[10000, 12000, 11000, 17000]), "▁▃▂█");
+        assert_eq!(sparkline(&[500, 500, 500]), "▅▅▅");
+    }
+}
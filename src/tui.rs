@@ -0,0 +1,500 @@
+//! `--tui` dashboard: the current lookup, a scrollable history, and session totals.
+//! Lookups and status lines reach it over a channel, so the rest of the program just calls
+//! `send` and falls back to printing when the dashboard isn't running.
+use std::sync::mpsc::Sender;
+
+use once_cell::sync::OnceCell;
+
+use crate::report::Lookup;
+
+pub enum Event {
+    Lookup(Lookup),
+    Status(String),
+}
+
+static EVENTS: OnceCell<Sender<Event>> = OnceCell::new();
+
+pub fn is_active() -> bool {
+    EVENTS.get().is_some()
+}
+
+/// Hand an event to the dashboard. If it isn't running, the event comes back as the error.
+pub fn send(event: Event) -> Result<(), Event> {
+    match EVENTS.get() {
+        Some(tx) => tx.send(event).map_err(|e| e.0),
+        None => Err(event),
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+pub fn run(_requery: fn(Lookup)) -> std::io::Result<()> {
+    Err(std::io::Error::other("built without the tui feature"))
+}
+
+#[cfg(feature = "tui")]
+pub use dashboard::run;
+
+#[cfg(feature = "tui")]
+mod dashboard {
+    use std::{sync::mpsc, time::Duration};
+
+    use chrono::Local;
+    use ratatui::{
+        crossterm::event::{self, KeyCode, KeyEventKind},
+        layout::{Constraint, Layout},
+        style::{Color, Modifier, Style, Stylize},
+        text::{Line, Span},
+        widgets::{Block, List, ListItem, ListState, Paragraph},
+        Frame,
+    };
+
+    use super::{Event, EVENTS};
+    use crate::{
+        config,
+        currency::Currency,
+        details::Details,
+        format_value,
+        lang::fill,
+        report::{FleaStatus, ItemReport, Lookup},
+        ruble_value, tier_color, tiers, trend,
+    };
+
+    #[derive(Default)]
+    pub(super) struct Dashboard {
+        /// oldest first
+        history: Vec<Lookup>,
+        /// index into `history`, `None` follows the newest lookup
+        selected: Option<usize>,
+        details: bool,
+        status: String,
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    pub(super) struct Totals {
+        pub lookups: usize,
+        /// what everything looked up this session would sell to traders for
+        pub trader: i64,
+        /// and on the flea at the lowest price, after tax
+        pub flea: i64,
+    }
+
+    impl Dashboard {
+        pub fn apply(&mut self, event: Event) {
+            match event {
+                Event::Lookup(lookup) => self.history.push(lookup),
+                Event::Status(msg) => self.status = msg,
+            }
+        }
+
+        fn selected_index(&self) -> Option<usize> {
+            self.selected.or(self.history.len().checked_sub(1))
+        }
+
+        pub fn selected_lookup(&self) -> Option<&Lookup> {
+            self.selected_index().map(|i| &self.history[i])
+        }
+
+        pub fn select_older(&mut self) {
+            self.selected = self.selected_index().map(|i| i.saturating_sub(1));
+        }
+
+        pub fn select_newer(&mut self) {
+            let last = self.history.len().saturating_sub(1);
+            self.selected = match self.selected_index() {
+                // back at the newest entry, follow new lookups again
+                Some(i) if i + 1 >= last => None,
+                Some(i) => Some(i + 1),
+                None => None,
+            };
+        }
+
+        /// Totals only count the best match of each lookup
+        pub fn totals(&self) -> Totals {
+            let best: Vec<&ItemReport> = self
+                .history
+                .iter()
+                .filter_map(|l| l.reports.first())
+                .collect();
+            Totals {
+                lookups: self.history.len(),
+                trader: best.iter().map(|r| r.trader_rubles()).sum(),
+                flea: best
+                    .iter()
+                    .filter_map(|r| r.flea.first())
+                    .map(|f| f.net())
+                    .sum(),
+            }
+        }
+
+        pub fn draw(&self, frame: &mut Frame) {
+            let [main, totals, footer] = Layout::vertical([
+                Constraint::Min(8),
+                Constraint::Length(1),
+                Constraint::Length(1),
+            ])
+            .areas(frame.area());
+            let [history, current] =
+                Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                    .areas(main);
+
+            let items: Vec<ListItem> = self
+                .history
+                .iter()
+                .map(|lookup| {
+                    let time = lookup
+                        .at
+                        .with_timezone(&Local)
+                        .format("%H:%M:%S")
+                        .to_string();
+                    let mut line = vec![Span::raw(time).dark_gray(), Span::raw(" ")];
+                    match lookup.reports.first() {
+                        Some(report) => {
+                            line.push(Span::raw(format!("{:<10} ", report.short_name)));
+                            if let Some(offer) = report.flea.first() {
+                                line.extend(item_money(
+                                    offer.net(),
+                                    Currency::Rouble,
+                                    report.slots,
+                                ));
+                            }
+                        }
+                        None => line.push(Span::raw(format!("{} (no results)", lookup.title))),
+                    }
+                    ListItem::new(Line::from(line))
+                })
+                .collect();
+            let mut state = ListState::default().with_selected(self.selected_index());
+            frame.render_stateful_widget(
+                List::new(items)
+                    .block(Block::bordered().title("History"))
+                    .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+                history,
+                &mut state,
+            );
+
+            let (title, body) = match self.selected_lookup() {
+                Some(lookup) if self.details => ("Details", details_lines(lookup)),
+                Some(lookup) => (
+                    "Lookup",
+                    lookup.reports.iter().flat_map(report_lines).collect(),
+                ),
+                None => ("Lookup", vec![Line::raw("Press the price hotkey in game")]),
+            };
+            frame.render_widget(
+                Paragraph::new(body).block(Block::bordered().title(title)),
+                current,
+            );
+
+            let t = self.totals();
+            let mut line = vec![Span::raw(format!(" {} lookups   traders ", t.lookups))];
+            line.extend(money(t.trader, Currency::Rouble));
+            line.push(Span::raw("   flea "));
+            line.extend(money(t.flea, Currency::Rouble));
+            frame.render_widget(Paragraph::new(Line::from(line)), totals);
+
+            let help = " ↑/↓ history  enter details  r re-query  q quit   ";
+            frame.render_widget(
+                Paragraph::new(Line::from(vec![
+                    Span::raw(help).dark_gray(),
+                    Span::raw(&self.status),
+                ])),
+                footer,
+            );
+        }
+    }
+
+    fn color(c: colored::Color) -> Color {
+        match c {
+            colored::Color::Black => Color::Black,
+            colored::Color::Red => Color::Red,
+            colored::Color::Green => Color::Green,
+            colored::Color::Yellow => Color::Yellow,
+            colored::Color::Blue => Color::Blue,
+            colored::Color::Magenta => Color::Magenta,
+            colored::Color::Cyan => Color::Cyan,
+            colored::Color::White => Color::Gray,
+            colored::Color::BrightBlack => Color::DarkGray,
+            colored::Color::BrightRed => Color::LightRed,
+            colored::Color::BrightGreen => Color::LightGreen,
+            colored::Color::BrightYellow => Color::LightYellow,
+            colored::Color::BrightBlue => Color::LightBlue,
+            colored::Color::BrightMagenta => Color::LightMagenta,
+            colored::Color::BrightCyan => Color::LightCyan,
+            colored::Color::BrightWhite => Color::White,
+            colored::Color::TrueColor { r, g, b } => Color::Rgb(r, g, b),
+        }
+    }
+
+    fn money(value: i64, currency: Currency) -> Vec<Span<'static>> {
+        item_money(value, currency, 1)
+    }
+
+    /// The price of a whole item of `slots`, tiered per slot when the config says so
+    fn item_money(value: i64, currency: Currency, slots: i64) -> Vec<Span<'static>> {
+        let (fg, bg) = tier_color(ruble_value(value, currency), slots);
+        let mut style = Style::new();
+        if !tiers::no_color() {
+            style = style.fg(color(fg));
+            if let Some(bg) = bg {
+                style = style.bg(color(bg));
+            }
+        }
+        vec![
+            Span::styled(format_value(value), style),
+            Span::raw(currency.sign()),
+        ]
+    }
+
+    fn change(percent: f64) -> Span<'static> {
+        let text = trend::format_change(percent);
+        match trend::Direction::of(percent) {
+            trend::Direction::Up => Span::raw(text).green(),
+            trend::Direction::Down => Span::raw(text).red(),
+            trend::Direction::Flat => Span::raw(text).dark_gray(),
+        }
+    }
+
+    fn per_slot(report: &ItemReport, value: i64, currency: Currency) -> Vec<Span<'static>> {
+        match report.per_slot(value) {
+            Some(v) => {
+                let mut spans = vec![Span::raw(" (")];
+                spans.extend(money(v, currency));
+                spans.push(Span::raw(format!(" x {})", report.slots)));
+                spans
+            }
+            None => vec![],
+        }
+    }
+
+    fn report_lines(report: &ItemReport) -> Vec<Line<'static>> {
+        let text = config().language.strings();
+        let mut lines = vec![Line::from(vec![
+            Span::raw(report.name.clone()).red(),
+            Span::raw(format!(" ({})", report.short_name)).italic(),
+        ])];
+
+        for requirement in &report.needed {
+            lines.push(Line::from(vec![
+                Span::raw(format!("{:<12}", text.needed)),
+                Span::raw(fill(
+                    text.needed_for,
+                    &[&requirement.amount(), &requirement.needed_for],
+                ))
+                .yellow(),
+            ]));
+        }
+
+        if let Some(details) = &report.details {
+            let mut line = vec![
+                Span::raw(format!("{:<12}", details.label(text))),
+                Span::raw(details.summary(text, report.slots)).cyan(),
+            ];
+            if let Details::Container(container) = details {
+                if let Some(price) = container.per_slot_gained(report.unit_price(), report.slots) {
+                    line.push(Span::raw(" "));
+                    line.extend(money(price, Currency::Rouble));
+                    line.push(Span::raw("/slot"));
+                }
+            }
+            lines.push(Line::from(line));
+        }
+
+        let trader = &report.trader;
+        let mut line = vec![Span::raw(format!("{:<12}", trader.name))];
+        line.extend(item_money(trader.price, trader.currency, report.slots));
+        line.extend(per_slot(report, trader.price, trader.currency));
+        lines.push(Line::from(line));
+
+        for offer in &report.flea {
+            let mut line = vec![Span::raw(format!(
+                "{:<12}",
+                format!("{} {}", offer.basis.label(text), text.flea)
+            ))];
+            line.extend(item_money(offer.net(), Currency::Rouble, report.slots));
+            line.extend(per_slot(report, offer.net(), Currency::Rouble));
+            lines.push(Line::from(line));
+        }
+
+        if report.flea_status == FleaStatus::Open {
+            lines.push(Line::from(vec![
+                Span::raw(format!("{:<12}", text.trend)),
+                Span::raw(format!("{} ", text.avg_24h)),
+                change(report.diff24h),
+                Span::raw(format!("  {} ", text.avg_7d)),
+                change(report.diff7days),
+            ]));
+        }
+
+        lines.push(Line::raw(""));
+        lines
+    }
+
+    fn details_lines(lookup: &Lookup) -> Vec<Line<'static>> {
+        let text = config().language.strings();
+        let mut lines = vec![
+            Line::raw(format!(
+                "Time:    {}",
+                lookup.at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+            )),
+            Line::raw(format!("OCR:     {}", lookup.ocr_text)),
+            Line::raw(format!("Matched: {}", lookup.title)),
+            Line::raw(""),
+        ];
+
+        for report in &lookup.reports {
+            // name, any requirements and details, and the trader
+            let header = 2 + report.needed.len() + usize::from(report.details.is_some());
+            lines.extend(report_lines(report).into_iter().take(header));
+            for offer in &report.flea {
+                let mut line = vec![Span::raw(format!(
+                    "{:<12}",
+                    format!("{} {}", offer.basis.label(text), text.flea)
+                ))];
+                line.extend(item_money(offer.price, Currency::Rouble, report.slots));
+                line.push(Span::raw(" - "));
+                line.extend(money(offer.tax, Currency::Rouble));
+                line.push(Span::raw(" tax = "));
+                line.extend(item_money(offer.net(), Currency::Rouble, report.slots));
+                lines.push(Line::from(line));
+            }
+            lines.push(Line::raw(""));
+        }
+
+        lines
+    }
+
+    /// Run the dashboard until `q`. `requery` is called with the selected history entry on `r`.
+    pub fn run(requery: fn(Lookup)) -> std::io::Result<()> {
+        let (tx, rx) = mpsc::channel();
+        if EVENTS.set(tx).is_err() {
+            return Err(std::io::Error::other("dashboard is already running"));
+        }
+
+        let mut terminal = ratatui::init();
+        let mut dash = Dashboard::default();
+
+        let result = loop {
+            while let Ok(event) = rx.try_recv() {
+                dash.apply(event);
+            }
+
+            if let Err(e) = terminal.draw(|frame| dash.draw(frame)) {
+                break Err(e);
+            }
+
+            match event::poll(Duration::from_millis(100)) {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(e) => break Err(e),
+            }
+
+            let key = match event::read() {
+                Ok(event::Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+                Ok(_) => continue,
+                Err(e) => break Err(e),
+            };
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => break Ok(()),
+                KeyCode::Up | KeyCode::Char('k') => dash.select_older(),
+                KeyCode::Down | KeyCode::Char('j') => dash.select_newer(),
+                KeyCode::Enter => dash.details = !dash.details,
+                KeyCode::Char('r') => {
+                    if let Some(lookup) = dash.selected_lookup().cloned() {
+                        dash.status = format!("Re-querying {}...", lookup.title);
+                        requery(lookup);
+                    }
+                }
+                _ => {}
+            }
+        };
+
+        ratatui::restore();
+        result
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use ratatui::{backend::TestBackend, Terminal};
+
+        use super::*;
+        use crate::{apis::market::sample_items, report::PriceSource};
+
+        fn dashboard() -> Dashboard {
+            let items = sample_items();
+            let mut dash = Dashboard::default();
+            for item in &items[..3] {
+                let lookup = Lookup::new(
+                    item.short_name.clone(),
+                    item.name.clone(),
+                    0.5,
+                    PriceSource::Live,
+                    std::slice::from_ref(item),
+                );
+                dash.apply(Event::Lookup(lookup));
+            }
+            dash
+        }
+
+        fn screen(dash: &Dashboard) -> String {
+            let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+            terminal.draw(|frame| dash.draw(frame)).unwrap();
+            let buffer = terminal.backend().buffer();
+            buffer.content().iter().map(|cell| cell.symbol()).collect()
+        }
+
+        #[test]
+        fn selection_follows_newest_until_scrolled() {
+            let mut dash = dashboard();
+            assert_eq!(
+                dash.selected_lookup().unwrap().title,
+                "Golden rooster figurine"
+            );
+
+            dash.select_older();
+            dash.select_older();
+            dash.select_older();
+            assert_eq!(
+                dash.selected_lookup().unwrap().title,
+                "Bottle of water (0.6L)"
+            );
+
+            dash.select_newer();
+            dash.select_newer();
+            assert_eq!(dash.selected, None);
+
+            let items = sample_items();
+            dash.apply(Event::Lookup(Lookup::new(
+                String::new(),
+                "LEDX".into(),
+                0.5,
+                PriceSource::Live,
+                &items[3..4],
+            )));
+            assert_eq!(dash.selected_lookup().unwrap().title, "LEDX");
+        }
+
+        #[test]
+        fn totals_use_best_match() {
+            let t = dashboard().totals();
+            assert_eq!(t.lookups, 3);
+            assert_eq!(t.trader, 1544 + 195300 + 57057);
+        }
+
+        #[test]
+        fn draws_current_and_history() {
+            let mut dash = dashboard();
+            dash.apply(Event::Status("Bot ready".into()));
+            let text = screen(&dash);
+
+            assert!(text.contains("History"));
+            assert!(text.contains("Rooster"));
+            assert!(text.contains("Golden rooster figurine"));
+            assert!(text.contains("3 lookups"));
+            assert!(text.contains("Bot ready"));
+
+            dash.details = true;
+            assert!(screen(&dash).contains("OCR:     Rooster"));
+        }
+    }
+}
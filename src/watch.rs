@@ -0,0 +1,252 @@
+//! Watchlist: items to keep an eye on, and a background poller that raises an alert when one
+//! of their conditions starts holding.
+//!
+//! Alerts are edge triggered. A condition that's still true on the next poll stays quiet, and
+//! has to stop holding before it can fire again.
+use std::{collections::HashSet, thread, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{format_value, report::ItemReport};
+
+/// Where the poller gets prices from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchSource {
+    /// search the market api for each item, costs one api call per item per poll
+    #[default]
+    Api,
+    /// only look at prices we already have from recent lookups
+    Local,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// lowest flea price after tax is under this many rubles
+    FleaNetBelow(i64),
+    /// lowest flea price after tax is over this many rubles
+    FleaNetAbove(i64),
+    /// the best trader pays more than the flea nets
+    TraderBeatsFlea,
+}
+
+impl Condition {
+    /// Why the condition holds for `report`, or `None` when it doesn't
+    pub fn check(&self, report: &ItemReport) -> Option<String> {
+        let flea = report.flea.first().map(|offer| offer.net());
+        match (self, flea) {
+            (Condition::FleaNetBelow(limit), Some(net)) if net < *limit => Some(format!(
+                "flea net {}₽ is below {}₽",
+                format_value(net),
+                format_value(*limit)
+            )),
+            (Condition::FleaNetAbove(limit), Some(net)) if net > *limit => Some(format!(
+                "flea net {}₽ is above {}₽",
+                format_value(net),
+                format_value(*limit)
+            )),
+            (Condition::TraderBeatsFlea, Some(net)) if report.trader_rubles() > net => {
+                Some(format!(
+                    "{} pays {}₽, more than the flea's {}₽",
+                    report.trader.name,
+                    format_value(report.trader_rubles()),
+                    format_value(net)
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Watch {
+    /// item title, or its bsg id
+    pub item: String,
+    pub when: Condition,
+}
+
+impl Watch {
+    /// Whether `item` is a bsg id rather than a title
+    pub fn by_bsg_id(&self) -> bool {
+        self.item.len() == 24 && self.item.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+
+    pub fn matches(&self, report: &ItemReport) -> bool {
+        self.item.eq_ignore_ascii_case(&report.name)
+            || (!report.bsg_id.is_empty() && self.item == report.bsg_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub item: String,
+    pub message: String,
+    pub report: ItemReport,
+}
+
+/// Remembers which watches have already fired, so each only alerts on the way in
+#[derive(Debug, Default)]
+pub struct Watcher {
+    fired: HashSet<usize>,
+}
+
+impl Watcher {
+    /// Check watch number `index` against the latest prices for it. A poll without a flea
+    /// price for the item (a failed request, a banned item) says nothing about the condition,
+    /// so it leaves the watch as it was.
+    pub fn check(&mut self, index: usize, watch: &Watch, reports: &[ItemReport]) -> Option<Alert> {
+        let mut priced = reports
+            .iter()
+            .filter(|r| watch.matches(r) && !r.flea.is_empty())
+            .peekable();
+        priced.peek()?;
+        let hit = priced.find_map(|r| Some((r, watch.when.check(r)?)));
+
+        match hit {
+            Some((report, message)) if self.fired.insert(index) => Some(Alert {
+                item: report.name.clone(),
+                message,
+                report: report.clone(),
+            }),
+            Some(_) => None,
+            None => {
+                self.fired.remove(&index);
+                None
+            }
+        }
+    }
+}
+
+/// Poll every `interval`, forever. `prices` gets the current reports for a watch (from
+/// wherever `WatchSource` says), `notify` gets every alert.
+pub fn spawn(
+    watches: Vec<Watch>,
+    interval: Duration,
+    prices: impl Fn(&Watch) -> Vec<ItemReport> + Send + 'static,
+    notify: impl Fn(Alert) + Send + 'static,
+) {
+    if watches.is_empty() {
+        return;
+    }
+
+    thread::spawn(move || {
+        let mut watcher = Watcher::default();
+        loop {
+            for (i, watch) in watches.iter().enumerate() {
+                if let Some(alert) = watcher.check(i, watch, &prices(watch)) {
+                    notify(alert);
+                }
+            }
+            thread::sleep(interval);
+        }
+    });
+}
+
+/// Post an alert to a Discord or Slack style incoming webhook
+pub fn post_webhook(url: &str, alert: &Alert) -> Result<(), reqwest::Error> {
+    let text = format!("{}: {}", alert.item, alert.message);
+    reqwest::blocking::Client::new()
+        .post(url)
+        .timeout(Duration::from_secs(10))
+        .json(&serde_json::json!({ "content": text, "text": text }))
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::market::sample_items;
+
+    fn reports() -> Vec<ItemReport> {
+        sample_items().iter().map(ItemReport::new).collect()
+    }
+
+    #[test]
+    fn parse_watchlist() {
+        let watches: Vec<Watch> = serde_json::from_str(
+            r#"[
+                {"item": "LEDX Skin Transilluminator", "when": {"flea_net_below": 700000}},
+                {"item": "5c093e3486f77430cb02e593", "when": "trader_beats_flea"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(watches[0].when, Condition::FleaNetBelow(700000));
+        assert_eq!(watches[1].when, Condition::TraderBeatsFlea);
+    }
+
+    #[test]
+    fn conditions() {
+        let reports = reports();
+        let (ledx, rooster) = (&reports[3], &reports[2]);
+
+        assert_eq!(
+            Condition::FleaNetBelow(700000).check(ledx).as_deref(),
+            Some("flea net 652,273₽ is below 700,000₽")
+        );
+        assert_eq!(Condition::FleaNetAbove(700000).check(ledx), None);
+        assert_eq!(Condition::TraderBeatsFlea.check(ledx), None);
+        assert_eq!(
+            Condition::TraderBeatsFlea.check(rooster).as_deref(),
+            Some("Therapist pays 57,057₽, more than the flea's 55,223₽")
+        );
+        // no flea rows for the banned helmet, nothing to compare
+        assert_eq!(Condition::TraderBeatsFlea.check(&reports[4]), None);
+    }
+
+    #[test]
+    fn match_by_title_or_bsg_id() {
+        let reports = reports();
+        let watch = |item: &str| Watch {
+            item: item.to_owned(),
+            when: Condition::TraderBeatsFlea,
+        };
+        assert!(watch("golden rooster figurine").matches(&reports[2]));
+        assert!(watch(&reports[2].bsg_id).matches(&reports[2]));
+        assert!(!watch("Rooster").matches(&reports[2]));
+
+        assert!(watch(&reports[2].bsg_id).by_bsg_id());
+        assert!(!watch("LEDX Skin Transilluminator").by_bsg_id());
+    }
+
+    #[test]
+    fn alerts_fire_once_per_crossing() {
+        let mut reports = reports();
+        let watch = Watch {
+            item: "Golden rooster figurine".into(),
+            when: Condition::FleaNetBelow(60000),
+        };
+        let mut watcher = Watcher::default();
+
+        let alert = watcher.check(0, &watch, &reports).unwrap();
+        assert_eq!(alert.item, "Golden rooster figurine");
+        assert_eq!(
+            watcher.check(0, &watch, &reports),
+            None,
+            "still below, stay quiet"
+        );
+
+        assert_eq!(
+            watcher.check(0, &watch, &[]),
+            None,
+            "a failed poll isn't a crossing"
+        );
+        assert_eq!(
+            watcher.check(0, &watch, &reports),
+            None,
+            "so the api coming back doesn't fire it again"
+        );
+
+        reports[2].flea[0].price = 80000;
+        assert_eq!(watcher.check(0, &watch, &reports), None);
+
+        reports[2].flea[0].price = 50000;
+        assert!(
+            watcher.check(0, &watch, &reports).is_some(),
+            "crossed again"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! One background thread that runs hotkey actions in order, so mashing the hotkey can't start
+//! a pile of parallel OCR runs that all print over each other.
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::hotkeys::Action;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Submitted {
+    Queued,
+    /// Same action was already waiting, it will run once with the newest press
+    Coalesced,
+    /// Pressed again inside the debounce window, ignored
+    Debounced,
+    QueueFull,
+}
+
+/// Lets a long running action notice that a newer press of the same action replaced it
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    latest: Arc<AtomicU64>,
+    generation: u64,
+}
+
+impl CancelToken {
+    /// A token that is never cancelled
+    pub fn none() -> Self {
+        Self {
+            latest: Arc::new(AtomicU64::new(0)),
+            generation: 0,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.latest.load(Ordering::SeqCst) != self.generation
+    }
+}
+
+struct Job {
+    action: Action,
+    token: CancelToken,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<Job>,
+    last_press: HashMap<Action, Instant>,
+    latest: HashMap<Action, Arc<AtomicU64>>,
+    shutdown: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+pub struct Worker {
+    shared: Arc<Shared>,
+    capacity: usize,
+    debounce: Duration,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    /// Start the worker thread. `capacity` bounds how many actions can wait behind the
+    /// running one; presses of the same action closer than `debounce` are dropped.
+    pub fn spawn<F>(capacity: usize, debounce: Duration, handler: F) -> Self
+    where
+        F: Fn(Action, &CancelToken) + Send + 'static,
+    {
+        let shared = Arc::new(Shared::default());
+
+        let thread_shared = shared.clone();
+        let handle = thread::spawn(move || loop {
+            let job = {
+                let mut state = thread_shared.state.lock().unwrap();
+                loop {
+                    if let Some(job) = state.queue.pop_front() {
+                        break job;
+                    }
+                    if state.shutdown {
+                        return;
+                    }
+                    state = thread_shared.ready.wait(state).unwrap();
+                }
+            };
+
+            if !job.token.is_cancelled() {
+                handler(job.action, &job.token);
+            }
+        });
+
+        Self {
+            shared,
+            capacity,
+            debounce,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn submit(&self, action: Action) -> Submitted {
+        let mut state = self.shared.state.lock().unwrap();
+
+        let now = Instant::now();
+        if let Some(prev) = state.last_press.get(&action) {
+            if now.duration_since(*prev) < self.debounce {
+                return Submitted::Debounced;
+            }
+        }
+        state.last_press.insert(action, now);
+
+        let supersedes = action.supersedes_previous();
+        let already_queued = supersedes && state.queue.iter().any(|j| j.action == action);
+
+        if !already_queued && state.queue.len() >= self.capacity {
+            return Submitted::QueueFull;
+        }
+
+        let token = if supersedes {
+            // Bumping the generation cancels the running job for this action, if any
+            let latest = state.latest.entry(action).or_default().clone();
+            let generation = latest.fetch_add(1, Ordering::SeqCst) + 1;
+            CancelToken { latest, generation }
+        } else {
+            CancelToken::none()
+        };
+
+        if already_queued {
+            let job = state.queue.iter_mut().find(|j| j.action == action).unwrap();
+            job.token = token;
+            return Submitted::Coalesced;
+        }
+
+        state.queue.push_back(Job { action, token });
+        self.shared.ready.notify_one();
+        Submitted::Queued
+    }
+}
+
+impl Drop for Worker {
+    /// Finish whatever is queued, then stop the thread
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.ready.notify_one();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    type Log = Arc<Mutex<Vec<(Action, bool)>>>;
+
+    /// Worker whose first job blocks until `gate` is sent to, so tests can fill the queue
+    /// while something is "in flight".
+    fn blocked_worker(
+        capacity: usize,
+        debounce: Duration,
+    ) -> (Worker, mpsc::Sender<()>, mpsc::Receiver<()>, Log) {
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = mpsc::channel::<()>();
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let handler_log = log.clone();
+        let gate_rx = Mutex::new(gate_rx);
+        let worker = Worker::spawn(capacity, debounce, move |action, token| {
+            started_tx.send(()).unwrap();
+            gate_rx.lock().unwrap().recv().unwrap();
+            handler_log
+                .lock()
+                .unwrap()
+                .push((action, token.is_cancelled()));
+        });
+
+        (worker, gate_tx, started_rx, log)
+    }
+
+    #[test]
+    fn newer_press_cancels_and_coalesces() {
+        let (worker, gate, started, log) = blocked_worker(4, Duration::ZERO);
+
+        assert_eq!(worker.submit(Action::PriceUnderCursor), Submitted::Queued);
+        started.recv().unwrap();
+
+        assert_eq!(worker.submit(Action::RepeatLast), Submitted::Queued);
+        assert_eq!(worker.submit(Action::PriceUnderCursor), Submitted::Queued);
+        assert_eq!(
+            worker.submit(Action::PriceUnderCursor),
+            Submitted::Coalesced
+        );
+
+        for _ in 0..3 {
+            gate.send(()).unwrap();
+        }
+        drop(worker);
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                (Action::PriceUnderCursor, true),
+                (Action::RepeatLast, false),
+                (Action::PriceUnderCursor, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn queue_is_bounded() {
+        let (worker, gate, started, log) = blocked_worker(1, Duration::ZERO);
+
+        assert_eq!(worker.submit(Action::RepeatLast), Submitted::Queued);
+        started.recv().unwrap();
+
+        assert_eq!(worker.submit(Action::PriceUnderCursor), Submitted::Queued);
+        assert_eq!(worker.submit(Action::ToggleOverlay), Submitted::QueueFull);
+        // coalescing into a waiting job doesn't need a free slot
+        assert_eq!(
+            worker.submit(Action::PriceUnderCursor),
+            Submitted::Coalesced
+        );
+
+        gate.send(()).unwrap();
+        gate.send(()).unwrap();
+        drop(worker);
+
+        assert_eq!(log.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn repeated_presses_are_debounced() {
+        let (worker, gate, started, _log) = blocked_worker(4, Duration::from_secs(3600));
+
+        assert_eq!(worker.submit(Action::PriceUnderCursor), Submitted::Queued);
+        started.recv().unwrap();
+        assert_eq!(
+            worker.submit(Action::PriceUnderCursor),
+            Submitted::Debounced
+        );
+        assert_eq!(worker.submit(Action::RepeatLast), Submitted::Queued);
+
+        gate.send(()).unwrap();
+        gate.send(()).unwrap();
+    }
+}